@@ -0,0 +1,128 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use x11::xlib;
+
+const SIZES: &[(u32, u32)] = &[(1920, 1080), (3840, 2160)];
+
+/// A client-side 32bpp BGRX image, the same layout `XGetImage`/`XShmGetImage` return on
+/// a 24 bit TrueColor visual. `XInitImage` sets up the pixel accessors without needing a
+/// connection to a server.
+fn synthetic_image(width: u32, height: u32, data: &mut [u8]) -> xlib::XImage {
+    let mut image: xlib::XImage = unsafe { std::mem::zeroed() };
+    image.width = width as _;
+    image.height = height as _;
+    image.format = xlib::ZPixmap;
+    image.data = data.as_mut_ptr() as _;
+    image.byte_order = xlib::LSBFirst;
+    image.bitmap_unit = 32;
+    image.bitmap_bit_order = xlib::LSBFirst;
+    image.bitmap_pad = 32;
+    image.depth = 24;
+    image.bytes_per_line = (width * 4) as _;
+    image.bits_per_pixel = 32;
+    image.red_mask = 0xff0000;
+    image.green_mask = 0x00ff00;
+    image.blue_mask = 0x0000ff;
+
+    assert_ne!(unsafe { xlib::XInitImage(&mut image) }, 0);
+
+    image
+}
+
+/// The conversion `screenshot()` used before MIT-SHM: one `XGetPixel` call per pixel.
+fn xgetpixel(image: &mut xlib::XImage) -> Vec<u8> {
+    let (width, height) = (image.width, image.height);
+    let (red_mask, green_mask, blue_mask) = (image.red_mask, image.green_mask, image.blue_mask);
+
+    let mut buf: Vec<u8> = vec![0; (width * height) as usize * 4];
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = unsafe { xlib::XGetPixel(image, x, y) };
+
+            let rgb: [u8; 4] = [
+                ((pixel & red_mask) >> red_mask.trailing_zeros()) as _,
+                ((pixel & green_mask) >> green_mask.trailing_zeros()) as _,
+                ((pixel & blue_mask) >> blue_mask.trailing_zeros()) as _,
+                255,
+            ];
+
+            let index = ((height - 1 - y) * width + x) as usize * 4;
+            buf[index..index + 4].copy_from_slice(&rgb);
+        }
+    }
+
+    buf
+}
+
+fn convert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("convert");
+    group.sample_size(10);
+
+    for &(width, height) in SIZES {
+        let mut data: Vec<u8> = (0..width * height * 4).map(|i| i as u8).collect();
+        let mut image = synthetic_image(width, height, &mut data);
+        let id = format!("{width}x{height}");
+
+        // BGRX is uploaded as is now, the GPU does the swizzle and flip
+        assert_eq!(
+            unsafe { monocle::screenshot::convert(&image) }.unwrap(),
+            (data.clone(), monocle::capture::PixelFormat::Bgra8)
+        );
+
+        group.bench_with_input(BenchmarkId::new("xgetpixel", &id), &(), |b, _| {
+            b.iter(|| xgetpixel(&mut image))
+        });
+
+        group.bench_with_input(BenchmarkId::new("bulk", &id), &(), |b, _| {
+            b.iter(|| unsafe { monocle::screenshot::convert(&image).unwrap() })
+        });
+    }
+
+    group.finish();
+}
+
+/// Reads the whole root window through both request paths, skipped without a display.
+fn get_image(c: &mut Criterion) {
+    if std::env::var_os("DISPLAY").is_none() {
+        return;
+    }
+
+    let display = unsafe { xlib::XOpenDisplay(std::ptr::null()) };
+    if display.is_null() {
+        return;
+    }
+
+    let screen = unsafe { xlib::XDefaultScreen(display) };
+    let root = unsafe { xlib::XRootWindow(display, screen) };
+    let visual = unsafe { xlib::XDefaultVisual(display, screen) };
+    let depth = unsafe { xlib::XDefaultDepth(display, screen) };
+    let width = unsafe { xlib::XDisplayWidth(display, screen) } as u32;
+    let height = unsafe { xlib::XDisplayHeight(display, screen) } as u32;
+    let id = format!("{width}x{height}");
+
+    let mut group = c.benchmark_group("get_image");
+    group.sample_size(10);
+
+    group.bench_with_input(BenchmarkId::new("xgetimage", &id), &(), |b, _| {
+        b.iter(|| {
+            unsafe { monocle::screenshot::get_image(display, root, 0, 0, width, height) }.unwrap()
+        })
+    });
+
+    // only the request and the copy out of the segment are timed, not setting it up
+    if let Some(mut shm) =
+        unsafe { monocle::screenshot::ShmImage::new(display, visual, depth as _, width, height) }
+    {
+        group.bench_with_input(BenchmarkId::new("xshmgetimage", &id), &(), |b, _| {
+            b.iter(|| {
+                assert!(unsafe { shm.capture(root, 0, 0) });
+                shm.convert().unwrap()
+            })
+        });
+    }
+
+    group.finish();
+    unsafe { xlib::XCloseDisplay(display) };
+}
+
+criterion_group!(benches, convert, get_image);
+criterion_main!(benches);
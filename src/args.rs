@@ -0,0 +1,436 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::*;
+
+use crate::camera::{self, ZoomAnchor};
+use crate::screenshot::Target;
+use crate::{FlashlightShape, FlashlightSpace, TouchpadScroll};
+
+const USAGE: &str = "\
+usage: monocle [options] [file]
+
+Shows the screen, or the PNG, JPEG, BMP, WebP, TIFF or PNM image in `file`
+(`-` reads it from stdin).
+
+options:
+    --monitor <n>     capture the n-th monitor (counted from 0, left to right)
+    --all-monitors    capture every monitor
+    --window <xid>    capture a single window, by X window id (decimal or 0x hex)
+    --pick-window     capture a single window, chosen by clicking on it
+    --test-pattern    show a generated test pattern instead of the screen
+    --select          start by selecting the region to zoom into
+    --cursor          show the mouse cursor in the capture (toggle with `c`)
+    --live            keep re-capturing the screen so the view follows what's on it
+    --interval <ms>   how often live mode re-captures (default: 33)
+    --grid-zoom <n>   zoom level above which the pixel grid fades in (default: 8)
+    --zoom-anchor <cursor|center>
+                      what stays put when zooming, the image under the mouse
+                      pointer or the middle of the window (default: cursor)
+    --acceleration <n>
+                      zoom speed added per line scrolled, in zoom levels per
+                      second (default: 0.6)
+    --friction <n>    how quickly zooming slows down, higher stops sooner
+                      (default: 3)
+    --touchpad <pan|zoom>
+                      what scrolling with two fingers does (default: pan on
+                      macOS, zoom elsewhere)
+    --flashlight-dim <0-1>
+                      how much the flashlight dims everything else (default: 0.9)
+    --flashlight-color <#rrggbb>
+                      what the flashlight dims towards (default: #000000)
+    --flashlight-softness <px>
+                      width of the flashlight's edge (default: 0)
+    --flashlight-shape <circle|ellipse|rounded-rect>
+                      shape of the flashlight (default: circle)
+    --flashlight-space <window|image>
+                      whether the flashlight follows the mouse at a fixed size on
+                      screen, or sits on the image and zooms with it (default: window)
+    --delay <secs>    wait before capturing, also how long the `d` countdown waits
+                      (default: 3)
+    -h, --help        print this message";
+
+/// Where the image comes from.
+pub enum Input {
+    Screen(Target),
+    File(PathBuf),
+    Stdin,
+    TestPattern,
+}
+
+pub struct Args {
+    pub input: Input,
+    /// start in region selection mode
+    pub select: bool,
+    /// start with the mouse cursor blended into the capture
+    pub cursor: bool,
+    /// keep re-capturing the screen
+    pub live: bool,
+    /// how often live mode re-captures
+    pub interval: Duration,
+    /// how long to wait before capturing
+    pub delay: Option<Duration>,
+    /// the pixel grid fades in above this zoom level
+    pub grid_zoom: f32,
+    pub zoom_anchor: ZoomAnchor,
+    /// zoom speed added per line scrolled, per second
+    pub acceleration: f32,
+    /// how quickly the zoom speed dies down, per second
+    pub friction: f32,
+    pub touchpad_scroll: TouchpadScroll,
+    /// how much the flashlight dims everything outside it, 0 to 1
+    pub flashlight_dim: f32,
+    /// linear RGB
+    pub flashlight_color: [f32; 3],
+    /// width of the flashlight's edge in window pixels
+    pub flashlight_softness: f32,
+    pub flashlight_shape: FlashlightShape,
+    pub flashlight_space: FlashlightSpace,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    fn parse_from(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut target = Target::Pointer;
+        let mut input = None;
+        let mut select = false;
+        let mut cursor = false;
+        let mut live = false;
+        let mut interval = Duration::from_millis(33);
+        let mut delay = None;
+        let mut grid_zoom = 8.0;
+        let mut zoom_anchor = ZoomAnchor::Cursor;
+        let mut acceleration = camera::DEFAULT_ACCELERATION;
+        let mut friction = camera::DEFAULT_FRICTION;
+        let mut touchpad_scroll = TouchpadScroll::default();
+        let mut flashlight_dim = 0.9;
+        let mut flashlight_color = [0.0; 3];
+        let mut flashlight_softness = 0.0;
+        let mut flashlight_shape = FlashlightShape::Circle;
+        let mut flashlight_space = FlashlightSpace::Window;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--monitor" => {
+                    let n = args.next().context("--monitor expects a monitor number")?;
+                    let n = n
+                        .parse()
+                        .with_context(|| format!("invalid monitor number `{n}`"))?;
+
+                    target = Target::Monitor(n);
+                }
+
+                "--all-monitors" => target = Target::All,
+                "--pick-window" => target = Target::PickWindow,
+
+                "--window" => {
+                    let id = args.next().context("--window expects a window id")?;
+                    let parsed = match id.strip_prefix("0x") {
+                        Some(hex) => u64::from_str_radix(hex, 16),
+                        None => id.parse(),
+                    };
+
+                    target = Target::Window(
+                        parsed.with_context(|| format!("invalid window id `{id}`"))?,
+                    );
+                }
+
+                "--test-pattern" => input = Some(Input::TestPattern),
+                "--select" => select = true,
+                "--cursor" => cursor = true,
+                "--live" => live = true,
+
+                "--interval" => {
+                    let ms = args.next().context("--interval expects milliseconds")?;
+                    let ms = ms
+                        .parse()
+                        .with_context(|| format!("invalid interval `{ms}`"))?;
+
+                    interval = Duration::from_millis(ms);
+                }
+
+                "--delay" => {
+                    let secs = args.next().context("--delay expects seconds")?;
+                    let secs = secs
+                        .parse()
+                        .ok()
+                        .and_then(|secs| Duration::try_from_secs_f32(secs).ok())
+                        .with_context(|| format!("invalid delay `{secs}`"))?;
+
+                    delay = Some(secs);
+                }
+
+                "--grid-zoom" => {
+                    let zoom = args.next().context("--grid-zoom expects a zoom level")?;
+
+                    grid_zoom = zoom
+                        .parse()
+                        .ok()
+                        .filter(|zoom: &f32| zoom.is_finite() && *zoom > 0.0)
+                        .with_context(|| format!("invalid zoom level `{zoom}`"))?;
+                }
+
+                "--zoom-anchor" => {
+                    let anchor = args.next().context("--zoom-anchor expects an anchor")?;
+
+                    zoom_anchor = match anchor.as_str() {
+                        "cursor" => ZoomAnchor::Cursor,
+                        "center" => ZoomAnchor::Center,
+                        _ => bail!("invalid anchor `{anchor}`, expected cursor or center"),
+                    };
+                }
+
+                "--acceleration" => {
+                    let n = args.next().context("--acceleration expects a number")?;
+
+                    acceleration = n
+                        .parse()
+                        .ok()
+                        .filter(|n: &f32| n.is_finite() && *n > 0.0)
+                        .with_context(|| format!("invalid acceleration `{n}`"))?;
+                }
+
+                "--friction" => {
+                    let n = args.next().context("--friction expects a number")?;
+
+                    // zero would never stop
+                    friction = n
+                        .parse()
+                        .ok()
+                        .filter(|n: &f32| n.is_finite() && *n > 0.0)
+                        .with_context(|| format!("invalid friction `{n}`"))?;
+                }
+
+                "--touchpad" => {
+                    let scroll = args.next().context("--touchpad expects pan or zoom")?;
+
+                    touchpad_scroll = match scroll.as_str() {
+                        "pan" => TouchpadScroll::Pan,
+                        "zoom" => TouchpadScroll::Zoom,
+                        _ => bail!("invalid touchpad scrolling `{scroll}`, expected pan or zoom"),
+                    };
+                }
+
+                "--flashlight-dim" => {
+                    let dim = args.next().context("--flashlight-dim expects a number")?;
+
+                    flashlight_dim = dim
+                        .parse()
+                        .ok()
+                        .filter(|dim| (0.0..=1.0).contains(dim))
+                        .with_context(|| format!("invalid dim amount `{dim}`, expected 0 to 1"))?;
+                }
+
+                "--flashlight-color" => {
+                    let color = args.next().context("--flashlight-color expects a colour")?;
+
+                    flashlight_color = parse_color(&color)
+                        .with_context(|| format!("invalid colour `{color}`, expected #rrggbb"))?;
+                }
+
+                "--flashlight-softness" => {
+                    let px = args
+                        .next()
+                        .context("--flashlight-softness expects pixels")?;
+
+                    flashlight_softness = px
+                        .parse()
+                        .ok()
+                        .filter(|px: &f32| px.is_finite() && *px >= 0.0)
+                        .with_context(|| format!("invalid softness `{px}`"))?;
+                }
+
+                "--flashlight-shape" => {
+                    let shape = args.next().context("--flashlight-shape expects a shape")?;
+
+                    flashlight_shape = match shape.as_str() {
+                        "circle" => FlashlightShape::Circle,
+                        "ellipse" => FlashlightShape::Ellipse,
+                        "rounded-rect" => FlashlightShape::RoundedRect,
+                        _ => bail!(
+                            "invalid shape `{shape}`, expected circle, ellipse or rounded-rect"
+                        ),
+                    };
+                }
+
+                "--flashlight-space" => {
+                    let space = args.next().context("--flashlight-space expects a space")?;
+
+                    flashlight_space = match space.as_str() {
+                        "window" => FlashlightSpace::Window,
+                        "image" => FlashlightSpace::Image,
+                        _ => bail!("invalid space `{space}`, expected window or image"),
+                    };
+                }
+
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+
+                "-" => input = Some(Input::Stdin),
+
+                _ if arg.starts_with('-') => bail!("unknown argument `{arg}`\n\n{USAGE}"),
+
+                _ => input = Some(Input::File(arg.into())),
+            }
+        }
+
+        let input = input.unwrap_or(Input::Screen(target));
+        if live && !matches!(input, Input::Screen(_)) {
+            bail!("--live only works when capturing the screen");
+        }
+
+        Ok(Self {
+            input,
+            select,
+            cursor,
+            live,
+            interval,
+            delay,
+            grid_zoom,
+            zoom_anchor,
+            acceleration,
+            friction,
+            touchpad_scroll,
+            flashlight_dim,
+            flashlight_color,
+            flashlight_softness,
+            flashlight_shape,
+            flashlight_space,
+        })
+    }
+}
+
+/// Parses `#rrggbb` (the `#` is optional) into linear RGB.
+fn parse_color(color: &str) -> Option<[f32; 3]> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    // from_str_radix alone would also take a sign
+    if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |i: usize| {
+        let srgb = u8::from_str_radix(&hex[i..i + 2], 16).ok()? as f32 / 255.0;
+
+        // the shader works in linear RGB, the surface converts back
+        Some(match srgb <= 0.04045 {
+            true => srgb / 12.92,
+            false => ((srgb + 0.055) / 1.055).powf(2.4),
+        })
+    };
+
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args> {
+        Args::parse_from(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        assert!(
+            a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-4),
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn color_is_linear() {
+        assert_close(parse_color("#000000").unwrap(), [0.0; 3]);
+        assert_close(parse_color("#ffffff").unwrap(), [1.0; 3]);
+
+        // the linear segment at the bottom, and the curve: sRGB 0.5 is about 0.214 linear
+        assert_close(
+            parse_color("#0a8000").unwrap(),
+            [10.0 / 255.0 / 12.92, 0.2158605, 0.0],
+        );
+    }
+
+    #[test]
+    fn color_channel_order() {
+        assert_close(parse_color("#ff0000").unwrap(), [1.0, 0.0, 0.0]);
+        assert_close(parse_color("#00ff00").unwrap(), [0.0, 1.0, 0.0]);
+        assert_close(parse_color("#0000FF").unwrap(), [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn color_hash_is_optional() {
+        assert_eq!(parse_color("336699"), parse_color("#336699"));
+        assert!(parse_color("##336699").is_none());
+    }
+
+    #[test]
+    fn color_length() {
+        assert!(parse_color("").is_none());
+        assert!(parse_color("#").is_none());
+        assert!(parse_color("#fff").is_none());
+        assert!(parse_color("#33669").is_none());
+        assert!(parse_color("#3366990").is_none());
+        assert!(parse_color("#336699ff").is_none());
+    }
+
+    #[test]
+    fn color_rejects_non_hex() {
+        assert!(parse_color("#33669g").is_none());
+        assert!(parse_color("#+12345").is_none());
+        // six bytes, but not six characters
+        assert!(parse_color("#é1234").is_none());
+    }
+
+    #[test]
+    fn flashlight_color_flag() {
+        let args = parse(&["--flashlight-color", "#ffffff"]).unwrap();
+        assert_close(args.flashlight_color, [1.0; 3]);
+
+        assert!(parse(&["--flashlight-color", "white"]).is_err());
+        assert!(parse(&["--flashlight-color"]).is_err());
+    }
+
+    #[test]
+    fn flashlight_dim_range() {
+        assert_eq!(
+            parse(&["--flashlight-dim", "0"]).unwrap().flashlight_dim,
+            0.0
+        );
+        assert_eq!(
+            parse(&["--flashlight-dim", "1"]).unwrap().flashlight_dim,
+            1.0
+        );
+        assert_eq!(
+            parse(&["--flashlight-dim", "0.5"]).unwrap().flashlight_dim,
+            0.5
+        );
+
+        assert!(parse(&["--flashlight-dim", "2"]).is_err());
+        assert!(parse(&["--flashlight-dim", "-0.1"]).is_err());
+        assert!(parse(&["--flashlight-dim", "NaN"]).is_err());
+    }
+
+    #[test]
+    fn friction_must_be_positive() {
+        assert_eq!(parse(&["--friction", "2"]).unwrap().friction, 2.0);
+
+        assert!(parse(&["--friction", "0"]).is_err());
+        assert!(parse(&["--friction", "-1"]).is_err());
+        assert!(parse(&["--friction", "inf"]).is_err());
+        assert!(parse(&["--friction", "fast"]).is_err());
+    }
+
+    #[test]
+    fn live_needs_the_screen() {
+        assert!(parse(&["--live"]).unwrap().live);
+        assert!(parse(&["--live", "--monitor", "1"]).unwrap().live);
+
+        assert!(parse(&["--live", "image.png"]).is_err());
+        assert!(parse(&["image.png", "--live"]).is_err());
+        assert!(parse(&["--live", "-"]).is_err());
+        assert!(parse(&["--live", "--test-pattern"]).is_err());
+    }
+}
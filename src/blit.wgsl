@@ -0,0 +1,31 @@
+// Copies one mip level into the next, smaller one. The linear sampler averages the
+// 2x2 texels under each destination texel.
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    // one triangle that covers the whole target
+    let uv = vec2(f32((index << 1u) & 2u), f32(index & 2u));
+
+    out.position = vec4(uv * vec2(2.0, -2.0) + vec2(-1.0, 1.0), 0.0, 1.0);
+    out.tex_coord = uv;
+
+    return out;
+}
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+
+@group(0) @binding(1)
+var s_source: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_source, s_source, in.tex_coord);
+}
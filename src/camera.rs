@@ -0,0 +1,522 @@
+use cgmath::{InnerSpace, Vector2};
+
+/// Zoom levels the camera stays between.
+pub const MIN_ZOOM: f32 = 0.01;
+pub const MAX_ZOOM: f32 = 100.0;
+
+/// Zoom speed added per line scrolled, in zoom levels per second.
+pub const DEFAULT_ACCELERATION: f32 = 0.6;
+
+/// How quickly the zoom speed dies down, per second. After `t` seconds `e^(-friction * t)`
+/// of it is left.
+pub const DEFAULT_FRICTION: f32 = 3.0;
+
+/// How quickly animations like `fit_to` close the gap to where they're going, per second.
+const EASING: f32 = 10.0;
+
+/// What stays put when zooming with the scroll wheel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoomAnchor {
+    /// the image point under the mouse pointer, like map viewers
+    Cursor,
+    /// the middle of the window
+    Center,
+}
+
+/// Which part of the image the window shows.
+///
+/// World space is the image in image pixels with y going up and the bottom left corner at
+/// the origin, that's where the quads are. Window positions are in window pixels with y
+/// going down, as the mouse reports them, and image pixels are counted from the top left
+/// like the pixel data.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    window_size: Vector2<f32>,
+    image_size: Vector2<f32>,
+
+    center: Vector2<f32>, // world point in the middle of the window
+    zoom: f32,            // window pixels per image pixel
+
+    velocity: f32,                     // zoom levels per second
+    goal: Option<(Vector2<f32>, f32)>, // (center, zoom) to animate towards
+
+    pub acceleration: f32,
+    pub friction: f32,
+    pub anchor: ZoomAnchor,
+}
+
+impl Camera {
+    /// A camera showing the middle of the image at 1:1.
+    pub fn new(window_size: (u32, u32), image_size: (u32, u32)) -> Self {
+        let mut camera = Self {
+            window_size: Vector2::new(window_size.0 as _, window_size.1 as _),
+            image_size: Vector2::new(image_size.0 as _, image_size.1 as _),
+
+            center: Vector2::new(0.0, 0.0),
+            zoom: 1.0,
+
+            velocity: 0.0,
+            goal: None,
+
+            acceleration: DEFAULT_ACCELERATION,
+            friction: DEFAULT_FRICTION,
+            anchor: ZoomAnchor::Cursor,
+        };
+
+        camera.reset();
+        camera
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Keeps the middle of the window on the same part of the image. Empty windows
+    /// (minimized ones) are ignored.
+    pub fn resize(&mut self, window_size: (u32, u32)) {
+        if window_size.0 > 0 && window_size.1 > 0 {
+            self.window_size = Vector2::new(window_size.0 as _, window_size.1 as _);
+        }
+    }
+
+    /// Swaps in an image of a different size, the view stays where it is.
+    pub fn set_image_size(&mut self, image_size: (u32, u32)) {
+        self.image_size = Vector2::new(image_size.0 as _, image_size.1 as _);
+    }
+
+    /// Back to the middle of the image at 1:1.
+    pub fn reset(&mut self) {
+        self.stop();
+        self.center = self.image_size / 2.0;
+        self.zoom = 1.0;
+    }
+
+    /// Stops gliding and animating.
+    pub fn stop(&mut self) {
+        self.velocity = 0.0;
+        self.goal = None;
+    }
+
+    /// Moves the image along with the mouse pointer, `delta` is in window pixels.
+    pub fn pan(&mut self, delta: Vector2<f32>) {
+        self.goal = None;
+        self.center -= flip_y(delta) / self.zoom;
+    }
+
+    /// Speeds up zooming in (or out, for negative `lines`) after scrolling.
+    pub fn scroll(&mut self, lines: f32) {
+        self.goal = None;
+        self.velocity += self.acceleration * lines;
+    }
+
+    /// Changes the zoom level right away, keeping the image point under `anchor` (in window
+    /// pixels) where it is.
+    pub fn zoom_at(&mut self, anchor: Vector2<f32>, zoom: f32) {
+        let zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        let world = self.window_to_world(anchor);
+
+        self.zoom = zoom;
+        self.center = world - flip_y(anchor - self.window_size / 2.0) / zoom;
+    }
+
+    /// Starts animating towards `zoom` around the middle of the window.
+    pub fn animate_zoom(&mut self, zoom: f32) {
+        let (center, _) = self.goal();
+
+        self.velocity = 0.0;
+        self.goal = Some((center, zoom.clamp(MIN_ZOOM, MAX_ZOOM)));
+    }
+
+    /// Like `animate_zoom`, relative to where the camera is going. Pressing a key again
+    /// before the animation is done adds up.
+    pub fn animate_zoom_by(&mut self, factor: f32) {
+        let (_, zoom) = self.goal();
+        self.animate_zoom(zoom * factor);
+    }
+
+    /// Starts animating a pan by `delta` window pixels, like `pan` it moves the image
+    /// along. Adds up like `animate_zoom_by`.
+    pub fn animate_pan(&mut self, delta: Vector2<f32>) {
+        let (center, zoom) = self.goal();
+
+        self.velocity = 0.0;
+        self.goal = Some((center - flip_y(delta) / zoom, zoom));
+    }
+
+    /// Starts animating so that the whole image fits in the window.
+    pub fn fit_image(&mut self) {
+        let zoom = (self.window_size.x / self.image_size.x)
+            .min(self.window_size.y / self.image_size.y)
+            .clamp(MIN_ZOOM, MAX_ZOOM);
+
+        self.velocity = 0.0;
+        self.goal = Some((self.image_size / 2.0, zoom));
+    }
+
+    /// Where the camera is animating to, or where it is if it isn't.
+    fn goal(&self) -> (Vector2<f32>, f32) {
+        self.goal.unwrap_or((self.center, self.zoom))
+    }
+
+    /// Starts animating so that the window rectangle `a`..`b` (in window pixels) fills the
+    /// window.
+    pub fn fit_to(&mut self, a: Vector2<f32>, b: Vector2<f32>) {
+        let a = self.window_to_world(a);
+        let b = self.window_to_world(b);
+
+        let zoom = (self.window_size.x / (b.x - a.x).abs())
+            .min(self.window_size.y / (b.y - a.y).abs())
+            .clamp(MIN_ZOOM, MAX_ZOOM);
+
+        self.velocity = 0.0;
+        self.goal = Some(((a + b) / 2.0, zoom));
+    }
+
+    /// Advances gliding and animations by `dt` seconds, `cursor` is the mouse pointer in
+    /// window pixels.
+    pub fn update(&mut self, dt: f32, cursor: Vector2<f32>) {
+        let anchor = match self.anchor {
+            ZoomAnchor::Cursor => cursor,
+            ZoomAnchor::Center => self.window_size / 2.0,
+        };
+
+        let zoom_change = glide(&mut self.velocity, self.friction, dt);
+        if zoom_change != 0.0 {
+            self.zoom_at(anchor, self.zoom + zoom_change);
+        }
+
+        if let Some((center, zoom)) = self.goal {
+            let easing = 1.0 - (-EASING * dt).exp();
+
+            self.center += (center - self.center) * easing;
+            self.zoom += (zoom - self.zoom) * easing;
+
+            // done within a thousandth of the zoom and half a window pixel
+            if (zoom - self.zoom).abs() < 0.001 * zoom
+                && (center - self.center).magnitude() * zoom < 0.5
+            {
+                self.center = center;
+                self.zoom = zoom;
+                self.goal = None;
+            }
+        }
+    }
+
+    /// Left, right, bottom and top edges of the visible area in world space.
+    pub fn view_bounds(&self) -> (f32, f32, f32, f32) {
+        let half = self.window_size / 2.0 / self.zoom;
+
+        (
+            self.center.x - half.x,
+            self.center.x + half.x,
+            self.center.y - half.y,
+            self.center.y + half.y,
+        )
+    }
+
+    /// World space to clip space.
+    pub fn projection(&self) -> cgmath::Matrix4<f32> {
+        let (left, right, bottom, top) = self.view_bounds();
+
+        cgmath::ortho(left, right, bottom, top, -1.0, 1.0)
+    }
+
+    /// The world point under `position`, in window pixels.
+    pub fn window_to_world(&self, position: Vector2<f32>) -> Vector2<f32> {
+        self.center + flip_y(position - self.window_size / 2.0) / self.zoom
+    }
+
+    pub fn world_to_window(&self, world: Vector2<f32>) -> Vector2<f32> {
+        self.window_size / 2.0 + flip_y(world - self.center) * self.zoom
+    }
+
+    /// The image pixel under `position` (in window pixels), if there is one.
+    pub fn window_to_image(&self, position: Vector2<f32>) -> Option<(u32, u32)> {
+        let world = self.window_to_world(position);
+
+        let x = world.x.floor();
+        let y = (self.image_size.y - world.y).floor();
+
+        let inside = (0.0..self.image_size.x).contains(&x) && (0.0..self.image_size.y).contains(&y);
+
+        inside.then_some((x as u32, y as u32))
+    }
+
+    /// Where the point `image` (in image pixels, fractions allowed) is in the window.
+    pub fn image_to_window(&self, image: Vector2<f32>) -> Vector2<f32> {
+        self.world_to_window(Vector2::new(image.x, self.image_size.y - image.y))
+    }
+}
+
+/// Window and world space y axes point opposite ways.
+fn flip_y(v: Vector2<f32>) -> Vector2<f32> {
+    Vector2::new(v.x, -v.y)
+}
+
+/// Slows `velocity` down by `friction` over `dt` seconds, returns how far it went meanwhile.
+pub fn glide(velocity: &mut f32, friction: f32, dt: f32) -> f32 {
+    let decay = (-friction * dt).exp();
+    let distance = *velocity * (1.0 - decay) / friction;
+
+    *velocity *= decay;
+    distance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: (u32, u32) = (800, 600);
+    const IMAGE: (u32, u32) = (200, 100);
+
+    fn assert_close(a: Vector2<f32>, b: Vector2<f32>) {
+        assert!((a - b).magnitude() < 1e-3, "{a:?} != {b:?}");
+    }
+
+    /// Runs `seconds` of updates at `fps` with the mouse pointer at `cursor`.
+    fn run(camera: &mut Camera, seconds: f32, fps: f32, cursor: Vector2<f32>) {
+        for _ in 0..(seconds * fps).round() as u32 {
+            camera.update(1.0 / fps, cursor);
+        }
+    }
+
+    #[test]
+    fn starts_centered_at_one_to_one() {
+        let camera = Camera::new(WINDOW, IMAGE);
+
+        assert_eq!(camera.zoom(), 1.0);
+        assert_close(
+            camera.image_to_window(Vector2::new(100.0, 50.0)),
+            Vector2::new(400.0, 300.0),
+        );
+        assert_close(
+            camera.image_to_window(Vector2::new(0.0, 0.0)),
+            Vector2::new(300.0, 250.0),
+        );
+        assert_eq!(
+            camera.window_to_image(Vector2::new(300.5, 250.5)),
+            Some((0, 0))
+        );
+    }
+
+    #[test]
+    fn window_and_image_round_trip_at_any_zoom() {
+        let mut camera = Camera::new(WINDOW, IMAGE);
+        let point = Vector2::new(123.25, 45.75);
+
+        for zoom in [MIN_ZOOM, 0.5, 1.0, 7.0, MAX_ZOOM] {
+            camera.zoom_at(Vector2::new(10.0, 20.0), zoom);
+
+            let window = camera.image_to_window(point);
+            let world = camera.window_to_world(window);
+            assert_close(camera.world_to_window(world), window);
+
+            // the top left of the pixel is where the next one starts, aim for the middle
+            let middle = camera.image_to_window(Vector2::new(123.5, 45.5));
+            assert_eq!(camera.window_to_image(middle), Some((123, 45)));
+        }
+    }
+
+    #[test]
+    fn outside_the_image_is_no_pixel() {
+        let camera = Camera::new(WINDOW, IMAGE);
+
+        assert_eq!(camera.window_to_image(Vector2::new(0.0, 0.0)), None);
+        assert_eq!(camera.window_to_image(Vector2::new(299.5, 300.0)), None);
+        assert_eq!(camera.window_to_image(Vector2::new(500.5, 300.0)), None);
+        assert_eq!(camera.window_to_image(Vector2::new(400.0, 350.5)), None);
+    }
+
+    #[test]
+    fn panning_moves_the_image_with_the_mouse() {
+        let mut camera = Camera::new(WINDOW, IMAGE);
+        camera.zoom_at(Vector2::new(400.0, 300.0), 4.0);
+
+        let point = Vector2::new(20.0, 30.0);
+        let before = camera.image_to_window(point);
+
+        camera.pan(Vector2::new(15.0, -40.0));
+
+        assert_close(
+            camera.image_to_window(point),
+            before + Vector2::new(15.0, -40.0),
+        );
+    }
+
+    #[test]
+    fn zooming_keeps_the_anchor_in_place() {
+        let mut camera = Camera::new(WINDOW, IMAGE);
+        let anchor = Vector2::new(310.0, 260.0);
+        let world = camera.window_to_world(anchor);
+
+        for zoom in [3.0, MAX_ZOOM, MIN_ZOOM, 1.0] {
+            camera.zoom_at(anchor, zoom);
+            assert_close(camera.world_to_window(world), anchor);
+        }
+    }
+
+    #[test]
+    fn scrolling_zooms_around_the_cursor_or_the_middle() {
+        let cursor = Vector2::new(700.0, 100.0);
+
+        let mut camera = Camera::new(WINDOW, IMAGE);
+        let world = camera.window_to_world(cursor);
+        camera.scroll(3.0);
+        run(&mut camera, 2.0, 60.0, cursor);
+
+        assert!(camera.zoom() > 1.5);
+        assert_close(camera.world_to_window(world), cursor);
+
+        let mut camera = Camera::new(WINDOW, IMAGE);
+        camera.anchor = ZoomAnchor::Center;
+        camera.scroll(3.0);
+        run(&mut camera, 2.0, 60.0, cursor);
+
+        assert_close(
+            camera.window_to_world(Vector2::new(400.0, 300.0)),
+            Vector2::new(100.0, 50.0),
+        );
+    }
+
+    #[test]
+    fn zoom_stays_within_limits() {
+        let mut camera = Camera::new(WINDOW, IMAGE);
+
+        camera.scroll(1e6);
+        run(&mut camera, 1.0, 60.0, Vector2::new(0.0, 0.0));
+        assert_eq!(camera.zoom(), MAX_ZOOM);
+
+        camera.stop();
+        camera.scroll(-1e6);
+        run(&mut camera, 1.0, 60.0, Vector2::new(0.0, 0.0));
+        assert_eq!(camera.zoom(), MIN_ZOOM);
+
+        // a rectangle without area would need infinite zoom
+        camera.fit_to(Vector2::new(10.0, 10.0), Vector2::new(10.0, 10.0));
+        run(&mut camera, 5.0, 60.0, Vector2::new(0.0, 0.0));
+        assert_eq!(camera.zoom(), MAX_ZOOM);
+        assert!(camera.center.x.is_finite() && camera.center.y.is_finite());
+    }
+
+    #[test]
+    fn gliding_is_the_same_at_any_frame_rate() {
+        let cursor = Vector2::new(400.0, 300.0);
+
+        let mut slow = Camera::new(WINDOW, IMAGE);
+        let mut fast = slow.clone();
+        slow.scroll(2.0);
+        fast.scroll(2.0);
+
+        run(&mut slow, 0.5, 60.0, cursor);
+        run(&mut fast, 0.5, 240.0, cursor);
+
+        assert!((slow.zoom() - fast.zoom()).abs() < 1e-4);
+        assert!(slow.zoom() > 1.0);
+    }
+
+    #[test]
+    fn fitting_a_rectangle_fills_the_window() {
+        let mut camera = Camera::new(WINDOW, IMAGE);
+
+        // the image's left half, twice as wide as tall so it fills the window's width
+        let a = camera.image_to_window(Vector2::new(0.0, 25.0));
+        let b = camera.image_to_window(Vector2::new(100.0, 75.0));
+        camera.fit_to(a, b);
+        run(&mut camera, 3.0, 60.0, Vector2::new(0.0, 0.0));
+
+        assert_eq!(camera.zoom(), 8.0);
+        assert_close(
+            camera.image_to_window(Vector2::new(0.0, 50.0)),
+            Vector2::new(0.0, 300.0),
+        );
+        assert_close(
+            camera.image_to_window(Vector2::new(100.0, 50.0)),
+            Vector2::new(800.0, 300.0),
+        );
+    }
+
+    #[test]
+    fn fitting_the_image() {
+        let mut camera = Camera::new(WINDOW, IMAGE);
+        camera.pan(Vector2::new(-250.0, 75.0));
+        camera.fit_image();
+        run(&mut camera, 3.0, 60.0, Vector2::new(0.0, 0.0));
+
+        // limited by the width
+        assert_eq!(camera.zoom(), 4.0);
+        assert_close(
+            camera.image_to_window(Vector2::new(0.0, 0.0)),
+            Vector2::new(0.0, 100.0),
+        );
+        assert_close(
+            camera.image_to_window(Vector2::new(200.0, 100.0)),
+            Vector2::new(800.0, 500.0),
+        );
+    }
+
+    #[test]
+    fn animations_add_up() {
+        let mut camera = Camera::new(WINDOW, IMAGE);
+        let point = Vector2::new(100.0, 50.0);
+
+        camera.animate_zoom_by(2.0);
+        camera.animate_zoom_by(2.0);
+        camera.animate_pan(Vector2::new(40.0, 0.0));
+        camera.animate_pan(Vector2::new(40.0, -20.0));
+        run(&mut camera, 3.0, 60.0, Vector2::new(0.0, 0.0));
+
+        assert_eq!(camera.zoom(), 4.0);
+        assert_close(camera.image_to_window(point), Vector2::new(480.0, 280.0));
+
+        // scrolling takes over from an animation
+        camera.animate_zoom_by(2.0);
+        camera.scroll(-1.0);
+        run(&mut camera, 3.0, 60.0, Vector2::new(400.0, 300.0));
+        assert!(camera.zoom() < 4.0);
+    }
+
+    #[test]
+    fn reset_goes_home() {
+        let mut camera = Camera::new(WINDOW, IMAGE);
+        let home = camera.projection();
+
+        camera.zoom_at(Vector2::new(0.0, 0.0), 42.0);
+        camera.pan(Vector2::new(100.0, 100.0));
+        camera.scroll(5.0);
+        camera.reset();
+        run(&mut camera, 1.0, 60.0, Vector2::new(0.0, 0.0));
+
+        assert_eq!(camera.projection(), home);
+    }
+
+    #[test]
+    fn resizing_keeps_the_middle() {
+        let mut camera = Camera::new(WINDOW, IMAGE);
+        camera.zoom_at(Vector2::new(100.0, 100.0), 3.0);
+        let middle = camera.window_to_world(Vector2::new(400.0, 300.0));
+
+        camera.resize((1920, 1080));
+        assert_close(camera.window_to_world(Vector2::new(960.0, 540.0)), middle);
+        assert_eq!(camera.zoom(), 3.0);
+
+        // minimized
+        camera.resize((0, 0));
+        assert_close(camera.window_to_world(Vector2::new(960.0, 540.0)), middle);
+    }
+
+    #[test]
+    fn projection_maps_the_window_to_clip_space() {
+        let mut camera = Camera::new(WINDOW, IMAGE);
+        camera.zoom_at(Vector2::new(123.0, 456.0), 2.5);
+
+        let clip = |window: Vector2<f32>| {
+            let world = camera.window_to_world(window);
+            let clip = camera.projection() * cgmath::Vector4::new(world.x, world.y, 0.0, 1.0);
+            Vector2::new(clip.x, clip.y)
+        };
+
+        assert_close(clip(Vector2::new(0.0, 0.0)), Vector2::new(-1.0, 1.0));
+        assert_close(clip(Vector2::new(800.0, 600.0)), Vector2::new(1.0, -1.0));
+        assert_close(clip(Vector2::new(400.0, 300.0)), Vector2::new(0.0, 0.0));
+    }
+}
@@ -0,0 +1,529 @@
+use std::io::Read;
+use std::path::PathBuf;
+
+pub use crate::screenshot::X11Source;
+
+#[derive(Debug)]
+pub enum CaptureError {
+    /// `XOpenDisplay` failed, usually because `DISPLAY` is unset or the server is unreachable.
+    NoDisplay,
+    /// The server refused to hand over the screen contents.
+    GetImage,
+    /// The screen uses a visual we can't turn into RGB.
+    UnsupportedVisual { class: i32, depth: i32 },
+    /// The server sent pixels laid out in a way we can't convert.
+    UnsupportedPixelFormat {
+        bits_per_pixel: i32,
+        masks: [u64; 3],
+    },
+    /// `--monitor` asked for a monitor that isn't connected.
+    NoSuchMonitor { index: usize, count: usize },
+    /// `--window` got an id that isn't a window.
+    NoSuchWindow(u64),
+    /// The window is unmapped or entirely off screen.
+    WindowNotViewable(u64),
+    /// Someone else has the pointer grabbed, so we can't let the user pick a window.
+    GrabPointer,
+    /// The user cancelled picking a window.
+    Cancelled,
+    /// Reading an image file or stdin failed.
+    Io(std::io::Error),
+    /// The input is not an image we can decode.
+    Decode(image::ImageError),
+}
+
+impl std::fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoDisplay => write!(
+                f,
+                "could not connect to the X server, is the DISPLAY environment variable set?"
+            ),
+            Self::GetImage => write!(f, "could not get the screen contents from the X server"),
+            Self::UnsupportedVisual { class, depth } => write!(
+                f,
+                "unsupported visual (class {class}, depth {depth}), only TrueColor and DirectColor are supported"
+            ),
+            Self::UnsupportedPixelFormat {
+                bits_per_pixel,
+                masks: [red, green, blue],
+            } => write!(
+                f,
+                "unsupported pixel format ({bits_per_pixel} bits per pixel, red mask {red:#x}, green mask {green:#x}, blue mask {blue:#x})"
+            ),
+            Self::NoSuchMonitor { index, count } => write!(
+                f,
+                "there is no monitor {index}, only {count} monitor(s) are connected (counted from 0)"
+            ),
+            Self::NoSuchWindow(window) => write!(f, "there is no window with id {window:#x}"),
+            Self::WindowNotViewable(window) => {
+                write!(f, "window {window:#x} is not visible on the screen")
+            }
+            Self::GrabPointer => write!(f, "could not grab the pointer to pick a window"),
+            Self::Cancelled => write!(f, "cancelled"),
+            Self::Io(e) => write!(f, "could not read the image: {e}"),
+            Self::Decode(e) => write!(f, "could not decode the image: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for CaptureError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// 8 bits per channel, in R, G, B, A byte order
+    Rgba8,
+    /// 8 bits per channel, in B, G, R, X byte order, what X servers use on little endian
+    /// machines. The fourth byte is padding, not alpha.
+    Bgra8,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> u32 {
+        match self {
+            Self::Rgba8 | Self::Bgra8 => 4,
+        }
+    }
+}
+
+pub struct Frame {
+    /// top left corner of the captured area in X root window coordinates, if the frame
+    /// came from the screen
+    pub position: Option<(i32, i32)>,
+    /// the X window the pixels were read from, if not the root window
+    pub window: Option<u64>,
+    /// (width, height)
+    pub dimensions: (u32, u32),
+    pub format: PixelFormat,
+    /// tightly packed pixels, top row first
+    pub data: Vec<u8>,
+    /// the mouse cursor at the time of the capture, not part of `data`
+    pub cursor: Option<Cursor>,
+}
+
+/// A mouse cursor image.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Cursor {
+    /// top left corner (the pointer position minus the hotspot), relative to the top left
+    /// corner of the frame
+    pub position: (i32, i32),
+    /// (width, height)
+    pub dimensions: (u32, u32),
+    /// premultiplied, in the frame's format with the fourth byte as alpha, top row first
+    pub pixels: Vec<u8>,
+}
+
+/// The part of a frame under the cursor, with and without the cursor blended in. Both are
+/// laid out like `Frame::data`.
+pub struct CursorPatch {
+    /// top left corner, in frame pixels
+    pub origin: (u32, u32),
+    /// (width, height)
+    pub dimensions: (u32, u32),
+    pub with_cursor: Vec<u8>,
+    pub without_cursor: Vec<u8>,
+}
+
+impl Frame {
+    /// The RGB value of the pixel at (`x`, `y`), counted from the top left.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 3]> {
+        if x >= self.dimensions.0 || y >= self.dimensions.1 {
+            return None;
+        }
+
+        let bpp = self.format.bytes_per_pixel();
+        let index = ((y * self.dimensions.0 + x) * bpp) as usize;
+        let pixel = &self.data[index..index + bpp as usize];
+
+        Some(match self.format {
+            PixelFormat::Rgba8 => [pixel[0], pixel[1], pixel[2]],
+            PixelFormat::Bgra8 => [pixel[2], pixel[1], pixel[0]],
+        })
+    }
+
+    /// Replaces the `size` pixels starting at `origin` (counted from the top left) with
+    /// `pixels`, which must be in the frame's format. Whatever falls outside the frame is
+    /// left out.
+    pub fn write(&mut self, origin: (u32, u32), size: (u32, u32), pixels: &[u8]) {
+        let bpp = self.format.bytes_per_pixel() as usize;
+        let row = size.0 as usize * bpp;
+
+        let right = (origin.0 + size.0).min(self.dimensions.0);
+        let bottom = (origin.1 + size.1).min(self.dimensions.1);
+
+        if right <= origin.0 || bottom <= origin.1 {
+            return;
+        }
+
+        let width = (right - origin.0) as usize * bpp;
+
+        for (y, src) in pixels
+            .chunks_exact(row)
+            .take((bottom - origin.1) as usize)
+            .enumerate()
+        {
+            let start =
+                ((origin.1 as usize + y) * self.dimensions.0 as usize + origin.0 as usize) * bpp;
+
+            self.data[start..start + width].copy_from_slice(&src[..width]);
+        }
+    }
+
+    /// Blends the cursor over a copy of the pixels under it. `None` if there is no cursor
+    /// or it's outside the frame.
+    pub fn cursor_patch(&self) -> Option<CursorPatch> {
+        let cursor = self.cursor.as_ref()?;
+        let (frame_width, frame_height) = (self.dimensions.0 as i32, self.dimensions.1 as i32);
+        let (cursor_width, cursor_height) =
+            (cursor.dimensions.0 as i32, cursor.dimensions.1 as i32);
+
+        // clip to the frame
+        let left = cursor.position.0.max(0);
+        let top = cursor.position.1.max(0);
+        let right = (cursor.position.0 + cursor_width).min(frame_width);
+        let bottom = (cursor.position.1 + cursor_height).min(frame_height);
+
+        if right <= left || bottom <= top {
+            return None;
+        }
+
+        let bpp = self.format.bytes_per_pixel() as usize;
+        let width = (right - left) as usize;
+
+        let mut without_cursor = Vec::with_capacity(width * (bottom - top) as usize * bpp);
+        let mut with_cursor = Vec::with_capacity(without_cursor.capacity());
+
+        for y in top..bottom {
+            let start = (y as usize * frame_width as usize + left as usize) * bpp;
+            let pixels = &self.data[start..start + width * bpp];
+
+            without_cursor.extend_from_slice(pixels);
+
+            let cursor_row = (y - cursor.position.1) as usize;
+            let cursor_start =
+                (cursor_row * cursor_width as usize + (left - cursor.position.0) as usize) * 4;
+            let cursor_pixels = &cursor.pixels[cursor_start..cursor_start + width * 4];
+
+            // both are in the same channel order, so only alpha needs special treatment
+            for (dst, src) in pixels.chunks_exact(bpp).zip(cursor_pixels.chunks_exact(4)) {
+                let alpha = 255 - src[3] as u32;
+                let blend =
+                    |s: u8, d: u8| (s as u32 + (d as u32 * alpha + 127) / 255).min(255) as u8;
+
+                with_cursor.extend_from_slice(&[
+                    blend(src[0], dst[0]),
+                    blend(src[1], dst[1]),
+                    blend(src[2], dst[2]),
+                    dst[3],
+                ]);
+            }
+        }
+
+        Some(CursorPatch {
+            origin: (left as u32, top as u32),
+            dimensions: (width as u32, (bottom - top) as u32),
+            with_cursor,
+            without_cursor,
+        })
+    }
+}
+
+/// Something monocle can show, e.g. the screen or an image file.
+pub trait CaptureSource {
+    fn capture(&mut self) -> Result<Frame, CaptureError>;
+}
+
+/// Reads a PNG, JPEG, BMP, WebP, TIFF or PNM image from a file.
+pub struct FileSource {
+    pub path: PathBuf,
+}
+
+impl CaptureSource for FileSource {
+    fn capture(&mut self) -> Result<Frame, CaptureError> {
+        decode(&std::fs::read(&self.path)?)
+    }
+}
+
+/// Reads an image from stdin, e.g. `import -window root png:- | monocle -`.
+pub struct StdinSource;
+
+impl CaptureSource for StdinSource {
+    fn capture(&mut self) -> Result<Frame, CaptureError> {
+        let mut bytes = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut bytes)?;
+
+        decode(&bytes)
+    }
+}
+
+/// A generated colour gradient with a checkerboard on top, doesn't need anything but
+/// the CPU.
+pub struct TestPattern {
+    pub dimensions: (u32, u32),
+}
+
+impl Default for TestPattern {
+    fn default() -> Self {
+        Self {
+            dimensions: (1920, 1080),
+        }
+    }
+}
+
+impl CaptureSource for TestPattern {
+    fn capture(&mut self) -> Result<Frame, CaptureError> {
+        const CELL: u32 = 32;
+
+        let (width, height) = self.dimensions;
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+
+        for y in 0..height {
+            for x in 0..width {
+                let checker = ((x / CELL + y / CELL) % 2) as u8 * 64;
+
+                data.extend_from_slice(&[
+                    (x * 255 / width.max(1)) as u8 ^ checker,
+                    (y * 255 / height.max(1)) as u8 ^ checker,
+                    128 ^ checker,
+                    255,
+                ]);
+            }
+        }
+
+        Ok(Frame {
+            position: None,
+            window: None,
+            dimensions: self.dimensions,
+            format: PixelFormat::Rgba8,
+            data,
+            cursor: None,
+        })
+    }
+}
+
+/// Decodes any supported image format, guessed from the contents.
+fn decode(bytes: &[u8]) -> Result<Frame, CaptureError> {
+    let image = image::load_from_memory(bytes).map_err(CaptureError::Decode)?;
+
+    let image = image.into_rgba8();
+
+    Ok(Frame {
+        position: None,
+        window: None,
+        dimensions: image.dimensions(),
+        format: PixelFormat::Rgba8,
+        data: image.into_raw(),
+        cursor: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A frame over `pixels`, one row per inner slice.
+    fn frame(rows: &[&[[u8; 4]]], format: PixelFormat) -> Frame {
+        Frame {
+            position: None,
+            window: None,
+            dimensions: (rows[0].len() as u32, rows.len() as u32),
+            format,
+            data: rows
+                .iter()
+                .flat_map(|row| row.iter().flatten())
+                .copied()
+                .collect(),
+            cursor: None,
+        }
+    }
+
+    /// A `dimensions` sized cursor at `position` with every pixel set to `pixel`.
+    fn cursor(position: (i32, i32), dimensions: (u32, u32), pixel: [u8; 4]) -> Cursor {
+        Cursor {
+            position,
+            dimensions,
+            pixels: pixel.repeat((dimensions.0 * dimensions.1) as usize),
+        }
+    }
+
+    #[test]
+    fn test_pattern_has_the_requested_size() {
+        let frame = TestPattern {
+            dimensions: (37, 5),
+        }
+        .capture()
+        .unwrap();
+
+        assert_eq!(frame.dimensions, (37, 5));
+        assert_eq!(frame.format, PixelFormat::Rgba8);
+        assert_eq!(frame.data.len(), 37 * 5 * 4);
+        assert!(frame.data.chunks_exact(4).all(|pixel| pixel[3] == 255));
+    }
+
+    #[test]
+    fn png_is_decoded_top_row_first() {
+        let image = image::RgbaImage::from_raw(
+            2,
+            2,
+            vec![
+                1, 2, 3, 255, 4, 5, 6, 255, // top row
+                7, 8, 9, 255, 10, 11, 12, 128, // bottom row
+            ],
+        )
+        .unwrap();
+        let mut png = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut png, image::ImageFormat::Png).unwrap();
+
+        let frame = decode(png.get_ref()).unwrap();
+
+        assert_eq!(frame.dimensions, (2, 2));
+        assert_eq!(frame.format, PixelFormat::Rgba8);
+        assert_eq!(frame.data, image.into_raw());
+    }
+
+    #[test]
+    fn garbage_is_not_decoded() {
+        assert!(matches!(
+            decode(b"not an image"),
+            Err(CaptureError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn pixel_reads_rgb_in_either_format() {
+        let rgba = frame(&[&[[1, 2, 3, 255], [4, 5, 6, 255]]], PixelFormat::Rgba8);
+        let bgra = frame(&[&[[3, 2, 1, 0], [6, 5, 4, 0]]], PixelFormat::Bgra8);
+
+        for frame in [rgba, bgra] {
+            assert_eq!(frame.pixel(0, 0), Some([1, 2, 3]));
+            assert_eq!(frame.pixel(1, 0), Some([4, 5, 6]));
+            assert_eq!(frame.pixel(2, 0), None);
+            assert_eq!(frame.pixel(0, 1), None);
+        }
+    }
+
+    #[test]
+    fn write_replaces_only_the_given_area() {
+        let row: &[[u8; 4]] = &[[0; 4]; 3];
+        let mut frame = frame(&[row; 3], PixelFormat::Rgba8);
+
+        frame.write((1, 1), (2, 1), &[1, 1, 1, 1, 2, 2, 2, 2]);
+
+        assert_eq!(frame.pixel(0, 1), Some([0, 0, 0]));
+        assert_eq!(frame.pixel(1, 1), Some([1, 1, 1]));
+        assert_eq!(frame.pixel(2, 1), Some([2, 2, 2]));
+        assert!(frame.data[..3 * 4].iter().all(|&b| b == 0));
+        assert!(frame.data[2 * 3 * 4..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn write_leaves_out_what_is_outside_the_frame() {
+        let row: &[[u8; 4]] = &[[0; 4]; 2];
+        let mut frame = frame(&[row; 2], PixelFormat::Rgba8);
+
+        // 2x2 starting at the bottom right pixel, only one of them lands in the frame
+        frame.write((1, 1), (2, 2), &[[1; 4], [2; 4], [3; 4], [4; 4]].concat());
+        frame.write((2, 0), (1, 1), &[5; 4]);
+
+        assert_eq!(frame.pixel(1, 1), Some([1, 1, 1]));
+        assert_eq!(frame.data[..3 * 4], [0; 3 * 4]);
+    }
+
+    #[test]
+    fn cursor_off_the_top_left_is_clipped() {
+        let row: &[[u8; 4]] = &[[10, 20, 30, 255]; 4];
+        let mut frame = frame(&[row; 4], PixelFormat::Rgba8);
+        frame.cursor = Some(cursor((-2, -1), (3, 3), [0, 0, 0, 255]));
+
+        let patch = frame.cursor_patch().unwrap();
+
+        assert_eq!(patch.origin, (0, 0));
+        assert_eq!(patch.dimensions, (1, 2));
+        assert_eq!(patch.without_cursor, [10, 20, 30, 255].repeat(2));
+        assert_eq!(patch.with_cursor, [0, 0, 0, 255].repeat(2));
+    }
+
+    #[test]
+    fn cursor_off_the_bottom_right_is_clipped() {
+        let row: &[[u8; 4]] = &[[10, 20, 30, 255]; 4];
+        let mut frame = frame(&[row; 4], PixelFormat::Rgba8);
+        frame.cursor = Some(cursor((2, 3), (3, 3), [0, 0, 0, 255]));
+
+        let patch = frame.cursor_patch().unwrap();
+
+        assert_eq!(patch.origin, (2, 3));
+        assert_eq!(patch.dimensions, (2, 1));
+        assert_eq!(patch.without_cursor, [10, 20, 30, 255].repeat(2));
+        assert_eq!(patch.with_cursor, [0, 0, 0, 255].repeat(2));
+    }
+
+    #[test]
+    fn cursor_outside_the_frame_has_no_patch() {
+        let row: &[[u8; 4]] = &[[0; 4]; 2];
+        let mut frame = frame(&[row; 2], PixelFormat::Rgba8);
+
+        frame.cursor = Some(cursor((2, 0), (3, 3), [0, 0, 0, 255]));
+        assert!(frame.cursor_patch().is_none());
+
+        frame.cursor = Some(cursor((-3, -3), (3, 3), [0, 0, 0, 255]));
+        assert!(frame.cursor_patch().is_none());
+    }
+
+    #[test]
+    fn opaque_cursor_replaces_the_frame() {
+        let mut frame = frame(&[&[[10, 20, 30, 255]]], PixelFormat::Rgba8);
+        frame.cursor = Some(cursor((0, 0), (1, 1), [200, 100, 50, 255]));
+
+        let patch = frame.cursor_patch().unwrap();
+
+        assert_eq!(patch.with_cursor, [200, 100, 50, 255]);
+    }
+
+    #[test]
+    fn transparent_cursor_leaves_the_frame_alone() {
+        let mut frame = frame(&[&[[10, 20, 30, 255]]], PixelFormat::Rgba8);
+        frame.cursor = Some(cursor((0, 0), (1, 1), [0, 0, 0, 0]));
+
+        let patch = frame.cursor_patch().unwrap();
+
+        assert_eq!(patch.with_cursor, patch.without_cursor);
+        assert_eq!(patch.with_cursor, [10, 20, 30, 255]);
+    }
+
+    #[test]
+    fn half_transparent_cursor_is_blended() {
+        let mut frame = frame(&[&[[200, 100, 0, 255]]], PixelFormat::Rgba8);
+        // premultiplied, so half transparent white is 128 in every channel
+        frame.cursor = Some(cursor((0, 0), (1, 1), [128, 128, 128, 128]));
+
+        let patch = frame.cursor_patch().unwrap();
+
+        assert_eq!(patch.with_cursor, [228, 178, 128, 255]);
+    }
+
+    #[test]
+    fn bgra_cursor_keeps_the_padding_byte() {
+        // the fourth byte of a BGRX frame is padding, it must not be replaced by alpha
+        let mut frame = frame(&[&[[30, 20, 10, 0]]], PixelFormat::Bgra8);
+        frame.cursor = Some(cursor((0, 0), (1, 1), [50, 100, 200, 255]));
+
+        let patch = frame.cursor_patch().unwrap();
+
+        assert_eq!(patch.without_cursor, [30, 20, 10, 0]);
+        assert_eq!(patch.with_cursor, [50, 100, 200, 0]);
+    }
+}
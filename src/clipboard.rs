@@ -0,0 +1,231 @@
+use std::ffi::CString;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::mpsc;
+
+use x11::xlib;
+
+use crate::screenshot::Connection;
+
+/// Puts text on the X clipboard.
+///
+/// X has no clipboard storage, the owner has to hand the text to every program that pastes
+/// it. A background thread with its own connection does that until someone else copies
+/// something or monocle exits.
+#[derive(Default)]
+pub struct Clipboard {
+    server: Option<Server>,
+}
+
+/// The sending side of the background thread.
+struct Server {
+    texts: mpsc::Sender<String>,
+    /// written to after every text, the thread sleeps until this or its X connection has
+    /// something to read, dropping it (after `texts`) lets the thread exit
+    wake: OwnedFd,
+}
+
+impl Clipboard {
+    pub fn copy(&mut self, text: String) {
+        if self.server.is_none() {
+            self.server = spawn();
+        }
+
+        let Some(server) = &self.server else {
+            eprintln!("[Error] the clipboard thread could not be started");
+            return;
+        };
+
+        let sent = server.texts.send(text).is_ok()
+            && unsafe { libc::write(server.wake.as_raw_fd(), [0u8].as_ptr() as _, 1) } == 1;
+
+        if !sent {
+            // the thread gave up, it already said why
+            self.server = None;
+        }
+    }
+}
+
+fn spawn() -> Option<Server> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return None;
+    }
+
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || serve(receiver, read));
+
+    Some(Server {
+        texts: sender,
+        wake: write,
+    })
+}
+
+struct Atoms {
+    clipboard: xlib::Atom,
+    targets: xlib::Atom,
+    utf8_string: xlib::Atom,
+}
+
+fn serve(texts: mpsc::Receiver<String>, wake: OwnedFd) {
+    let Ok(connection) = Connection::open() else {
+        eprintln!("[Error] the clipboard could not connect to the X server");
+        return;
+    };
+
+    let display = connection.0;
+
+    let atom = |name: &str| unsafe {
+        let name = CString::new(name).unwrap();
+        xlib::XInternAtom(display, name.as_ptr(), xlib::False)
+    };
+
+    let atoms = Atoms {
+        clipboard: atom("CLIPBOARD"),
+        targets: atom("TARGETS"),
+        utf8_string: atom("UTF8_STRING"),
+    };
+
+    // selections belong to windows, this one is never mapped
+    let window = unsafe {
+        let root = xlib::XDefaultRootWindow(display);
+        xlib::XCreateSimpleWindow(display, root, 0, 0, 1, 1, 0, 0, 0)
+    };
+
+    let mut text: Option<String> = None;
+
+    let mut fds = [
+        libc::pollfd {
+            fd: wake.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        },
+        libc::pollfd {
+            fd: unsafe { xlib::XConnectionNumber(display) },
+            events: libc::POLLIN,
+            revents: 0,
+        },
+    ];
+
+    loop {
+        loop {
+            match texts.try_recv() {
+                Ok(new) => unsafe {
+                    xlib::XSetSelectionOwner(display, atoms.clipboard, window, xlib::CurrentTime);
+                    xlib::XFlush(display);
+
+                    text = Some(new);
+                },
+
+                Err(mpsc::TryRecvError::Empty) => break,
+
+                // the window is gone
+                Err(mpsc::TryRecvError::Disconnected) => return,
+            }
+        }
+
+        // Xlib may already have read events off the connection, those don't show up in poll
+        while unsafe { xlib::XPending(display) } > 0 {
+            let mut event: xlib::XEvent = unsafe { std::mem::zeroed() };
+            unsafe { xlib::XNextEvent(display, &mut event) };
+
+            match event.get_type() {
+                // someone else copied something
+                xlib::SelectionClear => text = None,
+
+                xlib::SelectionRequest => unsafe {
+                    reply(display, &atoms, text.as_deref(), &event.selection_request)
+                },
+
+                _ => {}
+            }
+        }
+
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, -1) } < 0 {
+            if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+
+            eprintln!("[Error] the clipboard stopped waiting for pastes");
+            return;
+        }
+
+        // one byte per text, the texts themselves are picked up above
+        if fds[0].revents & libc::POLLIN != 0 {
+            let mut bytes = [0u8; 64];
+            unsafe { libc::read(wake.as_raw_fd(), bytes.as_mut_ptr() as _, bytes.len()) };
+        }
+    }
+}
+
+/// Answers a paste, or a question about what can be pasted.
+unsafe fn reply(
+    display: *mut xlib::Display,
+    atoms: &Atoms,
+    text: Option<&str>,
+    request: &xlib::XSelectionRequestEvent,
+) {
+    // very old clients don't say where they want it
+    let property = match request.property {
+        0 => request.target,
+        property => property,
+    };
+
+    let mut notify = xlib::XSelectionEvent {
+        type_: xlib::SelectionNotify,
+        serial: 0,
+        send_event: xlib::True,
+        display,
+        requestor: request.requestor,
+        selection: request.selection,
+        target: request.target,
+        property: 0, // refused
+        time: request.time,
+    };
+
+    match text {
+        Some(_) if request.target == atoms.targets => {
+            let targets = [atoms.targets, atoms.utf8_string, xlib::XA_STRING];
+
+            xlib::XChangeProperty(
+                display,
+                request.requestor,
+                property,
+                xlib::XA_ATOM,
+                32,
+                xlib::PropModeReplace,
+                targets.as_ptr() as _,
+                targets.len() as _,
+            );
+
+            notify.property = property;
+        }
+
+        Some(text) if request.target == atoms.utf8_string || request.target == xlib::XA_STRING => {
+            xlib::XChangeProperty(
+                display,
+                request.requestor,
+                property,
+                request.target,
+                8,
+                xlib::PropModeReplace,
+                text.as_ptr(),
+                text.len() as _,
+            );
+
+            notify.property = property;
+        }
+
+        _ => {}
+    }
+
+    xlib::XSendEvent(
+        display,
+        request.requestor,
+        xlib::False,
+        0,
+        &mut xlib::XEvent::from(notify),
+    );
+    xlib::XFlush(display);
+}
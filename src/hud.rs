@@ -0,0 +1,284 @@
+use wgpu::util::DeviceExt;
+
+use crate::{Vertex, INDICES};
+
+/// Every glyph is 5x7, one byte per row with the leftmost pixel in bit 4.
+#[rustfmt::skip]
+const GLYPHS: &[(char, [u8; 7])] = &[
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('#', [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000]),
+];
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const ADVANCE: u32 = GLYPH_WIDTH + 1;
+const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+
+const PADDING: u32 = 3;
+const SWATCH: u32 = 2 * LINE_HEIGHT - 2;
+const TEXT_LEFT: u32 = PADDING + SWATCH + 4;
+
+/// Longest line the HUD has room for.
+const MAX_CHARS: u32 = 24;
+
+const WIDTH: u32 = TEXT_LEFT + MAX_CHARS * ADVANCE + PADDING;
+const HEIGHT: u32 = 2 * PADDING + SWATCH;
+
+/// HUD pixels are this many window pixels wide.
+const SCALE: f32 = 2.0;
+
+/// How far from the mouse pointer the HUD is drawn, in window pixels.
+const OFFSET: f32 = 20.0;
+
+/// A small box next to the mouse pointer with a colour swatch and two lines of text.
+pub struct Hud {
+    render_pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    texture: wgpu::Texture,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+
+    // what's in the texture, to skip redrawing it
+    contents: Option<([u8; 3], [String; 2])>,
+    width: u32, // of the part of the texture in use
+    visible: bool,
+}
+
+impl Hud {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("hud texture"),
+            size: wgpu::Extent3d {
+                width: WIDTH,
+                height: HEIGHT,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("hud.wgsl"));
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("HUD Pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[Vertex::BUFFER_LAYOUT],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("hud_bind_group"),
+            layout: &render_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HUD Vertex Buffer"),
+            size: 4 * std::mem::size_of::<Vertex>() as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("HUD Index Buffer"),
+            contents: bytemuck::cast_slice(INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            render_pipeline,
+            bind_group,
+            texture,
+            vertex_buffer,
+            index_buffer,
+
+            contents: None,
+            width: 0,
+            visible: false,
+        }
+    }
+
+    /// Shows `color` and `lines` next to `position` (in window pixels), or hides the HUD.
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        window_size: winit::dpi::PhysicalSize<u32>,
+        contents: Option<(cgmath::Vector2<f32>, [u8; 3], [String; 2])>,
+    ) {
+        let Some((position, color, lines)) = contents else {
+            self.visible = false;
+            return;
+        };
+
+        self.visible = true;
+
+        if self.contents.as_ref() != Some(&(color, lines.clone())) {
+            self.width = self.rasterize(queue, color, &lines);
+            self.contents = Some((color, lines));
+        }
+
+        let width = self.width as f32 * SCALE;
+        let height = HEIGHT as f32 * SCALE;
+        let window_width = window_size.width as f32;
+        let window_height = window_size.height as f32;
+
+        // below and to the right of the pointer, unless that's off the window
+        let mut left = position.x + OFFSET;
+        let mut top = position.y + OFFSET;
+        if left + width > window_width {
+            left = position.x - OFFSET - width;
+        }
+        if top + height > window_height {
+            top = position.y - OFFSET - height;
+        }
+
+        // whole window pixels, so the nearest sampler keeps every HUD pixel the same size
+        let (left, top) = (left.round(), top.round());
+
+        // window pixels to clip space, y goes up
+        let x = |x: f32| x / window_width * 2.0 - 1.0;
+        let y = |y: f32| 1.0 - y / window_height * 2.0;
+
+        let (l, r, t, b) = (x(left), x(left + width), y(top), y(top + height));
+        let u = self.width as f32 / WIDTH as f32;
+
+        #[rustfmt::skip]
+        let vertices = [
+            Vertex { position: [l, b, 0.0], tex_coords: [0.0, 1.0] }, // bottom left
+            Vertex { position: [r, b, 0.0], tex_coords: [  u, 1.0] }, // bottom right
+            Vertex { position: [r, t, 0.0], tex_coords: [  u, 0.0] }, // top right
+            Vertex { position: [l, t, 0.0], tex_coords: [0.0, 0.0] }, // top left
+        ];
+
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        if !self.visible {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..INDICES.len() as _, 0, 0..1);
+    }
+
+    /// Draws the HUD into its texture, returns how wide it turned out.
+    fn rasterize(&self, queue: &wgpu::Queue, color: [u8; 3], lines: &[String; 2]) -> u32 {
+        const BACKGROUND: [u8; 4] = [16, 16, 16, 220];
+        const TEXT: [u8; 4] = [255, 255, 255, 255];
+
+        let mut pixels = [BACKGROUND; (WIDTH * HEIGHT) as usize];
+        let mut set = |x: u32, y: u32, rgba: [u8; 4]| pixels[(y * WIDTH + x) as usize] = rgba;
+
+        for y in PADDING..PADDING + SWATCH {
+            for x in PADDING..PADDING + SWATCH {
+                set(x, y, [color[0], color[1], color[2], 255]);
+            }
+        }
+
+        let mut chars = 0;
+
+        for (line, text) in lines.iter().enumerate() {
+            let top = PADDING + line as u32 * LINE_HEIGHT;
+
+            for (i, c) in text.chars().take(MAX_CHARS as _).enumerate() {
+                let left = TEXT_LEFT + i as u32 * ADVANCE;
+
+                // anything we don't have a glyph for is a space
+                let Some((_, rows)) = GLYPHS.iter().find(|(glyph, _)| *glyph == c) else {
+                    continue;
+                };
+
+                for (y, row) in rows.iter().enumerate() {
+                    for x in 0..GLYPH_WIDTH {
+                        if row & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+                            set(left + x, top + y as u32, TEXT);
+                        }
+                    }
+                }
+            }
+
+            chars = chars.max(text.chars().count().min(MAX_CHARS as _) as u32);
+        }
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                aspect: wgpu::TextureAspect::All,
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            bytemuck::cast_slice(&pixels),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * WIDTH),
+                rows_per_image: Some(HEIGHT),
+            },
+            wgpu::Extent3d {
+                width: WIDTH,
+                height: HEIGHT,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        // no space after the last character
+        TEXT_LEFT + chars * ADVANCE - 1 + PADDING
+    }
+}
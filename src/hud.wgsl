@@ -0,0 +1,32 @@
+// Draws the HUD texture, the vertices are already in clip space.
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coord: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+}
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.position = vec4(model.position, 1.0);
+    out.tex_coord = model.tex_coord;
+
+    return out;
+}
+
+@group(0) @binding(0)
+var t_hud: texture_2d<f32>;
+
+@group(0) @binding(1)
+var s_hud: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_hud, s_hud, in.tex_coord);
+}
@@ -1,17 +1,31 @@
+use std::time::{Duration, Instant};
+
 use cgmath::Zero;
 use wgpu::util::DeviceExt;
 use winit::{
     dpi::{PhysicalPosition, PhysicalSize},
-    event::{ElementState, Event, KeyEvent, MouseButton, WindowEvent},
-    event_loop::EventLoop,
-    keyboard::{KeyCode, PhysicalKey},
+    event::{ElementState, Event, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    keyboard::{Key, KeyCode, PhysicalKey},
+    platform::modifier_supplement::KeyEventExtModifierSupplement,
     window::{Window, WindowBuilder},
 };
 
-mod screenshot;
+mod args;
+pub mod camera;
+pub mod capture;
+mod clipboard;
+mod hud;
+mod live;
+pub mod screenshot;
 mod texture;
 
-use screenshot::screenshot;
+use args::{Args, Input};
+use camera::{glide, Camera, ZoomAnchor};
+use capture::{CaptureSource, CursorPatch, FileSource, Frame, StdinSource, TestPattern, X11Source};
+use clipboard::Clipboard;
+use hud::Hud;
+use live::{LiveCapture, Rect};
 use texture::Texture;
 
 #[repr(C)]
@@ -32,16 +46,138 @@ impl Vertex {
     };
 }
 
+/// Indices of one quad, offset by 4 for every tile.
 const INDICES: &[u16] = &[0, 1, 2, 0, 2, 3];
 
+/// `Sampling::Auto` switches to nearest neighbour above this zoom level.
+const AUTO_NEAREST_ZOOM: f32 = 3.0;
+
+/// How long the countdown capture key waits if `--delay` wasn't given.
+const DEFAULT_CAPTURE_DELAY: Duration = Duration::from_secs(3);
+
+/// Hiding our window is asynchronous and whatever was underneath needs time to repaint, so
+/// the countdown never captures sooner than this.
+const MIN_CAPTURE_DELAY: Duration = Duration::from_millis(250);
+
+/// How far the arrow keys pan, as a fraction of the window. Shift pans further.
+const KEY_PAN_STEP: f32 = 0.1;
+const KEY_PAN_STEP_LARGE: f32 = 0.5;
+
+/// How much `+` and `-` zoom in or out. Shift zooms further.
+const KEY_ZOOM_STEP: f32 = 1.25;
+const KEY_ZOOM_STEP_LARGE: f32 = 2.0;
+
+/// Touchpads scroll by pixels (or fractions of a line on X11), this many make up a line of
+/// a scroll wheel.
+const PIXELS_PER_LINE: f32 = 50.0;
+
+/// Flashlight and loupe radius speed added per line scrolled, in window pixels per second.
+const RADIUS_ACCELERATION: f32 = 120.0;
+
+/// Loupe magnification speed added per line scrolled, per second.
+const MAGNIFICATION_ACCELERATION: f32 = 6.0;
+
+/// Like the camera's friction, for the flashlight and loupe.
+const RADIUS_FRICTION: f32 = 6.0;
+
+/// Longest step the camera takes in one frame. Frames after the window was hidden or
+/// stalled would otherwise jump.
+const MAX_FRAME_TIME: f32 = 0.1;
+
+/// Colours the `b` key cycles the flashlight's dim colour through, in linear RGB.
+const FLASHLIGHT_COLORS: &[[f32; 3]] = &[[0.0, 0.0, 0.0], [1.0, 1.0, 1.0], [0.01, 0.02, 0.06]];
+
+/// Edge widths the `e` key cycles through, in window pixels.
+const FLASHLIGHT_SOFTNESS: &[f32] = &[0.0, 20.0, 80.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashlightShape {
+    Circle,
+    Ellipse,
+    RoundedRect,
+}
+
+impl FlashlightShape {
+    fn next(self) -> Self {
+        match self {
+            Self::Circle => Self::Ellipse,
+            Self::Ellipse => Self::RoundedRect,
+            Self::RoundedRect => Self::Circle,
+        }
+    }
+}
+
+/// Where the flashlight lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashlightSpace {
+    /// follows the mouse pointer and keeps its size on screen
+    Window,
+    /// sits on the image under the mouse pointer and zooms with it
+    Image,
+}
+
+/// What scrolling with two fingers on a touchpad does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchpadScroll {
+    /// moves the image along with the fingers, pinching zooms (macOS only)
+    Pan,
+    /// zooms like a scroll wheel
+    Zoom,
+}
+
+impl Default for TouchpadScroll {
+    /// Panning only where pinching is there to zoom, elsewhere a touchpad would have no way
+    /// to zoom at all.
+    fn default() -> Self {
+        match cfg!(target_os = "macos") {
+            true => Self::Pan,
+            false => Self::Zoom,
+        }
+    }
+}
+
+/// How the image is filtered when magnified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sampling {
+    /// linear when zoomed out or slightly in, nearest neighbour above `AUTO_NEAREST_ZOOM`
+    Auto,
+    Linear,
+    Nearest,
+}
+
+impl Sampling {
+    fn next(self) -> Self {
+        match self {
+            Self::Auto => Self::Nearest,
+            Self::Nearest => Self::Linear,
+            Self::Linear => Self::Auto,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct Uniform {
     projection_matrix: [[f32; 4]; 4],
     mouse_position: [f32; 2],
-    flashlight: u32, // used as bool
-    flashlight_radius: f32,
-    _padding: f32,
+    flashlight: u32,        // used as bool
+    flashlight_radius: f32, // window pixels, or image pixels when anchored to the image
+    selection: [f32; 4],    // min x, min y, max x, max y in window pixels
+    selecting: u32,         // used as bool
+    grid: u32,              // used as bool
+    grid_zoom: f32,         // the pixel grid fades in above this zoom level
+    zoom: f32,
+    image_size: [f32; 2],
+    flashlight_dim: f32, // how much of `flashlight_color` to mix in outside the light
+    flashlight_softness: f32, // width of the edge in window pixels
+    flashlight_color: [f32; 4], // linear RGB, alpha unused
+    flashlight_shape: u32,
+    flashlight_space: u32,
+    flashlight_anchor: [f32; 2], // world space, only used when anchored to the image
+    loupe: u32,                  // used as bool
+    loupe_radius: f32,           // window pixels
+    loupe_magnification: f32,    // on top of the camera's zoom
+    _padding: u32,
 }
 
 #[allow(unused)]
@@ -60,27 +196,51 @@ struct State<'a> {
     uniform_bind_group: wgpu::BindGroup,
     uniform_buffer: wgpu::Buffer,
 
-    texture_bind_group: wgpu::BindGroup,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    texture_bind_groups: Vec<wgpu::BindGroup>, // one per tile
     texture: Texture,
+    sampling: Sampling,
+    filter: wgpu::FilterMode, // what the texture bind group currently samples with
+    live: Option<LiveCapture>,
+    live_interval: Option<Duration>, // set with --live
+    cursor: Option<CursorPatch>,
+    show_cursor: bool,
+    frame: Frame, // CPU copy of the image, without the cursor
+
+    hud: Hud,
+    picker: bool, // show the colour under the mouse pointer
+    clipboard: Clipboard,
+
+    source: Box<dyn CaptureSource>,
+    capture_delay: Duration,
+    capture_at: Option<Instant>, // when the countdown capture fires
 
     ctrl_key_held: bool,
+    shift_key_held: bool,
+    touchpad_scroll: TouchpadScroll,
 
-    // camera stuff
-    camera_velocity: f32,
-    camera_target: cgmath::Vector2<f32>, // origin
-    camera_zoom: f32,
+    camera: Camera,
+    last_update: Instant, // speeds are per second, this measures how long a frame took
     click_start_position: Option<cgmath::Vector2<f32>>,
+    selection_start: Option<cgmath::Vector2<f32>>,
     last_mouse_position: cgmath::Vector2<f32>,
     flashlight_radius_velocity: f32,
+    flashlight_anchor: Option<cgmath::Vector2<f32>>, // None re-anchors under the mouse pointer
+    loupe_radius_velocity: f32,
+    loupe_magnification_velocity: f32,
     //
-
     window: &'a Window,
 }
 
 impl<'a> State<'a> {
     // Creating some of the wgpu types requires async code
-    async fn new(window: &'a Window, screenshot: ((u32, u32), Vec<u8>)) -> State<'a> {
-        let ((width, height), _) = screenshot;
+    async fn new(
+        window: &'a Window,
+        source: Box<dyn CaptureSource>,
+        frame: Frame,
+        select: bool,
+    ) -> State<'a> {
+        let (width, height) = frame.dimensions;
 
         let size = window.inner_size();
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
@@ -102,7 +262,8 @@ impl<'a> State<'a> {
             .request_device(
                 &wgpu::DeviceDescriptor {
                     required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::default(),
+                    // as large textures as the GPU can do, bigger images get tiled
+                    required_limits: wgpu::Limits::default().using_resolution(adapter.limits()),
                     label: None,
                     memory_hints: Default::default(),
                 },
@@ -132,9 +293,17 @@ impl<'a> State<'a> {
 
         surface.configure(&device, &config);
 
-        let (dimensions, data) = screenshot;
-        let texture =
-            Texture::from_bytes(&device, &queue, &data, dimensions, "screenshot texture").unwrap();
+        let texture = Texture::from_bytes(
+            &device,
+            &queue,
+            &frame.data,
+            frame.dimensions,
+            frame.format,
+            "screenshot texture",
+        )
+        .unwrap();
+
+        let cursor = frame.cursor_patch();
 
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -159,20 +328,9 @@ impl<'a> State<'a> {
                 ],
             });
 
-        let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("texture_bind_group"),
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
-                },
-            ],
-        });
+        let filter = wgpu::FilterMode::Linear;
+        let texture_bind_groups =
+            create_texture_bind_groups(&device, &texture_bind_group_layout, &texture, filter);
 
         let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
 
@@ -181,7 +339,22 @@ impl<'a> State<'a> {
             mouse_position: [0.0; 2],
             flashlight: 0,
             flashlight_radius: 130.0,
-            _padding: 0.0,
+            selection: [0.0; 4],
+            selecting: select as _,
+            grid: 1,
+            grid_zoom: 8.0,
+            zoom: 1.0,
+            image_size: [width as _, height as _],
+            flashlight_dim: 0.9,
+            flashlight_softness: 0.0,
+            flashlight_color: [0.0, 0.0, 0.0, 1.0],
+            flashlight_shape: FlashlightShape::Circle as _,
+            flashlight_space: FlashlightSpace::Window as _,
+            flashlight_anchor: [0.0; 2],
+            loupe: 0,
+            loupe_radius: 150.0,
+            loupe_magnification: 4.0,
+            _padding: 0,
         };
 
         let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -259,28 +432,7 @@ impl<'a> State<'a> {
             cache: None,
         });
 
-        let sw = width as f32;
-        let sh = height as f32;
-
-        #[rustfmt::skip]
-        let vertices: &[Vertex] = &[
-            Vertex { position: [ 0.0,  0.0, 0.0], tex_coords: [0.0, 0.0] }, // bottom left
-            Vertex { position: [  sw,  0.0, 0.0], tex_coords: [1.0, 0.0] }, // bottom right
-            Vertex { position: [  sw,   sh, 0.0], tex_coords: [1.0, 1.0] }, // top right
-            Vertex { position: [ 0.0,   sh, 0.0], tex_coords: [0.0, 1.0] }, // top left
-        ];
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(INDICES),
-            usage: wgpu::BufferUsages::INDEX,
-        });
+        let (vertex_buffer, index_buffer) = create_quads(&device, &texture);
 
         Self {
             render_pipeline,
@@ -291,17 +443,38 @@ impl<'a> State<'a> {
             uniform_bind_group,
             uniform_buffer,
 
-            camera_velocity: 0.0,
-            camera_target: cgmath::Vector2 { x: 0.0, y: 0.0 },
-            camera_zoom: 1.0,
+            camera: Camera::new((size.width, size.height), frame.dimensions),
+            last_update: Instant::now(),
             click_start_position: None,
+            selection_start: None,
             last_mouse_position: cgmath::Vector2::zero(),
             flashlight_radius_velocity: 0.0,
+            flashlight_anchor: None,
+            loupe_radius_velocity: 0.0,
+            loupe_magnification_velocity: 0.0,
 
-            texture_bind_group,
+            texture_bind_group_layout,
+            texture_bind_groups,
             texture,
+            sampling: Sampling::Auto,
+            filter,
+            live: None,
+            live_interval: None,
+            cursor,
+            show_cursor: false,
+            frame,
+
+            hud: Hud::new(&device, config.format),
+            picker: true,
+            clipboard: Clipboard::default(),
+
+            source,
+            capture_delay: DEFAULT_CAPTURE_DELAY,
+            capture_at: None,
 
             ctrl_key_held: false,
+            shift_key_held: false,
+            touchpad_scroll: TouchpadScroll::default(),
 
             surface,
             device,
@@ -313,21 +486,222 @@ impl<'a> State<'a> {
         }
     }
 
+    /// Hides the window and captures again once `capture_delay` has passed, e.g. to let a
+    /// menu or tooltip open.
+    fn start_countdown(&mut self) {
+        self.capture_at = Some(Instant::now() + self.capture_delay.max(MIN_CAPTURE_DELAY));
+        self.window.set_visible(false);
+    }
+
+    /// Runs the countdown capture if it's due. Returns when it's due otherwise.
+    fn poll_countdown(&mut self) -> Option<Instant> {
+        let at = self.capture_at?;
+        if Instant::now() < at {
+            return Some(at);
+        }
+
+        self.capture_at = None;
+
+        match self.source.capture() {
+            Ok(frame) => self.load_frame(frame),
+            Err(e) => eprintln!("[Error] {e}"),
+        }
+
+        self.window.set_visible(true);
+
+        None
+    }
+
+    /// Replaces the image, keeping the camera where it is.
+    fn load_frame(&mut self, frame: Frame) {
+        let texture = Texture::from_bytes(
+            &self.device,
+            &self.queue,
+            &frame.data,
+            frame.dimensions,
+            frame.format,
+            "screenshot texture",
+        )
+        .unwrap();
+
+        self.texture_bind_groups = create_texture_bind_groups(
+            &self.device,
+            &self.texture_bind_group_layout,
+            &texture,
+            self.filter,
+        );
+        (self.vertex_buffer, self.index_buffer) = create_quads(&self.device, &texture);
+        self.texture = texture;
+
+        self.camera.set_image_size(frame.dimensions);
+
+        self.cursor = frame.cursor_patch();
+        self.frame = frame;
+        self.set_cursor_visible(self.show_cursor);
+
+        // the new frame may cover a different area, patches for the old one don't fit it
+        if self.live.is_some() {
+            self.start_live_capture();
+        }
+    }
+
+    /// Starts re-capturing the area the frame came from if `--live` was given, replacing
+    /// any earlier live capture.
+    fn start_live_capture(&mut self) {
+        self.live = None;
+
+        let (Some(interval), Some((x, y))) = (self.live_interval, self.frame.position) else {
+            return;
+        };
+
+        let area = Rect {
+            x,
+            y,
+            width: self.frame.dimensions.0,
+            height: self.frame.dimensions.1,
+        };
+
+        let live = LiveCapture::spawn(area, self.frame.window, self.frame.format, interval);
+        live.set_cursor_visible(self.show_cursor);
+
+        self.live = Some(live);
+        self.exclude_window_from_capture();
+    }
+
+    /// Blends the captured cursor into the texture, or restores the pixels under it.
+    fn set_cursor_visible(&mut self, visible: bool) {
+        self.show_cursor = visible;
+
+        if let Some(live) = &self.live {
+            live.set_cursor_visible(visible);
+        }
+
+        let Some(cursor) = &self.cursor else {
+            return;
+        };
+
+        let pixels = match visible {
+            true => &cursor.with_cursor,
+            false => &cursor.without_cursor,
+        };
+
+        self.texture
+            .write(&self.queue, cursor.origin, cursor.dimensions, pixels);
+        self.texture.generate_mipmaps(&self.device, &self.queue);
+    }
+
     pub fn window(&self) -> &Window {
         self.window
     }
 
+    /// Copies the colour under the mouse pointer as `#rrggbb`.
+    fn copy_color(&mut self) {
+        let Some((x, y)) = self.camera.window_to_image(self.last_mouse_position) else {
+            return;
+        };
+
+        if let Some([r, g, b]) = self.frame.pixel(x, y) {
+            self.clipboard.copy(format!("#{r:02x}{g:02x}{b:02x}"));
+        }
+    }
+
+    fn flashlight_space(&self) -> FlashlightSpace {
+        match self.uniform.flashlight_space {
+            1 => FlashlightSpace::Image,
+            _ => FlashlightSpace::Window,
+        }
+    }
+
+    /// Switches the flashlight between following the mouse and sitting on the image,
+    /// keeping its current size on screen.
+    fn set_flashlight_space(&mut self, space: FlashlightSpace) {
+        if space == self.flashlight_space() {
+            return;
+        }
+
+        self.uniform.flashlight_radius = match space {
+            FlashlightSpace::Window => self.uniform.flashlight_radius * self.camera.zoom(),
+            FlashlightSpace::Image => self.uniform.flashlight_radius / self.camera.zoom(),
+        };
+        self.uniform.flashlight_space = space as _;
+        self.flashlight_anchor = None;
+    }
+
+    fn flashlight_shape(&self) -> FlashlightShape {
+        match self.uniform.flashlight_shape {
+            1 => FlashlightShape::Ellipse,
+            2 => FlashlightShape::RoundedRect,
+            _ => FlashlightShape::Circle,
+        }
+    }
+
+    /// Picks the next of `options` after `current`, or the first if `current` isn't one.
+    fn cycle<T: Copy + PartialEq>(options: &[T], current: T) -> T {
+        let next = options
+            .iter()
+            .position(|&o| o == current)
+            .map_or(0, |i| i + 1);
+        options[next % options.len()]
+    }
+
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
+            self.camera.resize((new_size.width, new_size.height));
         }
+
+        self.exclude_window_from_capture();
+    }
+
+    /// Keeps live capture from picking up our own window.
+    fn exclude_window_from_capture(&self) {
+        let Some(live) = &self.live else {
+            return;
+        };
+
+        let rect = self.window.outer_position().ok().map(|position| {
+            let size = self.window.outer_size();
+
+            Rect {
+                x: position.x,
+                y: position.y,
+                width: size.width,
+                height: size.height,
+            }
+        });
+
+        live.exclude(rect);
     }
 
     fn input(&mut self, event: &WindowEvent) -> bool {
-        const CAMERA_ACCELERATION: f32 = 0.01;
+        // these go by the character the key types rather than where it is, `+` has a key of
+        // its own on e.g. German layouts where the US `=` key is
+        if let WindowEvent::KeyboardInput {
+            event:
+                event @ KeyEvent {
+                    state: ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            let unmodified = event.key_without_modifiers();
+
+            match symbol_key(&event.logical_key, &unmodified, self.shift_key_held) {
+                Some(SymbolKey::Zoom(factor)) => {
+                    self.camera.animate_zoom_by(factor);
+                    return true;
+                }
+                Some(SymbolKey::Fit) => {
+                    self.camera.fit_image();
+                    return true;
+                }
+                None => {}
+            }
+        }
 
         match event {
             WindowEvent::CursorMoved {
@@ -335,6 +709,33 @@ impl<'a> State<'a> {
                 ..
             } => {
                 self.last_mouse_position = cgmath::Vector2::new(*x as _, *y as _);
+                self.flashlight_anchor = None;
+            }
+
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if self.uniform.selecting == 1 => {
+                self.selection_start = Some(self.last_mouse_position);
+            }
+
+            WindowEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                ..
+            } if self.uniform.selecting == 1 => {
+                if let Some(start) = self.selection_start.take() {
+                    let end = self.last_mouse_position;
+
+                    // ignore plain clicks
+                    if (end.x - start.x).abs() >= 4.0 && (end.y - start.y).abs() >= 4.0 {
+                        self.camera.fit_to(start, end);
+                        self.uniform.selecting = 0;
+                        self.window
+                            .set_cursor_icon(winit::window::CursorIcon::Default);
+                    }
+                }
             }
 
             WindowEvent::MouseInput {
@@ -342,6 +743,7 @@ impl<'a> State<'a> {
                 button: MouseButton::Left,
                 ..
             } => {
+                self.camera.stop();
                 self.click_start_position = Some(self.last_mouse_position);
                 self.window
                     .set_cursor_icon(winit::window::CursorIcon::Grabbing);
@@ -357,26 +759,110 @@ impl<'a> State<'a> {
                     .set_cursor_icon(winit::window::CursorIcon::Default);
             }
 
-            WindowEvent::MouseWheel {
-                delta: winit::event::MouseScrollDelta::LineDelta(_, y),
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
                 ..
-            } => {
-                if self.ctrl_key_held {
-                    self.flashlight_radius_velocity += CAMERA_ACCELERATION * y * 200.0;
+            } => self.copy_color(),
+
+            WindowEvent::MouseWheel { delta, .. } => {
+                // two finger touchpad scrolling, the flashlight and loupe still take
+                // Ctrl+scroll
+                let pan = touchpad_delta(*delta).filter(|_| {
+                    self.touchpad_scroll == TouchpadScroll::Pan
+                        && self.uniform.loupe == 0
+                        && !self.ctrl_key_held
+                });
+
+                let y = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(PhysicalPosition { y, .. }) => {
+                        *y as f32 / PIXELS_PER_LINE
+                    }
+                };
+
+                if let Some(pixels) = pan {
+                    self.camera.pan(pixels)
+                } else if self.uniform.loupe == 1 {
+                    // the lens zooms instead of the view
+                    if self.ctrl_key_held {
+                        self.loupe_radius_velocity += RADIUS_ACCELERATION * y;
+                    } else {
+                        self.loupe_magnification_velocity += MAGNIFICATION_ACCELERATION * y;
+                    }
+                } else if self.ctrl_key_held {
+                    self.flashlight_radius_velocity += RADIUS_ACCELERATION * y;
                 } else {
-                    self.camera_velocity += CAMERA_ACCELERATION * y
+                    self.camera.scroll(y)
                 }
             }
 
+            // pinching, around the fingers which is where the mouse pointer is. winit only
+            // reports it on macOS
+            WindowEvent::TouchpadMagnify { delta, .. } => {
+                let factor = (1.0 + *delta as f32).max(0.1);
+
+                if self.uniform.loupe == 1 {
+                    self.uniform.loupe_magnification =
+                        (self.uniform.loupe_magnification * factor).clamp(1.5, 32.0);
+                } else {
+                    self.camera.stop();
+                    self.camera
+                        .zoom_at(self.last_mouse_position, self.camera.zoom() * factor);
+                }
+            }
+
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.shift_key_held = modifiers.state().shift_key();
+                self.ctrl_key_held = modifiers.state().control_key();
+            }
+
+            // arrow keys and vim keys pan, they're repeated while held
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
                         physical_key:
-                            PhysicalKey::Code(KeyCode::ControlLeft | KeyCode::ControlRight),
+                            PhysicalKey::Code(
+                                key @ (KeyCode::ArrowLeft
+                                | KeyCode::ArrowRight
+                                | KeyCode::ArrowUp
+                                | KeyCode::ArrowDown
+                                | KeyCode::KeyH
+                                | KeyCode::KeyJ
+                                | KeyCode::KeyK
+                                | KeyCode::KeyL),
+                            ),
+                        state: ElementState::Pressed,
                         ..
                     },
                 ..
-            } => self.ctrl_key_held = !self.ctrl_key_held,
+            } => {
+                // which way the image moves, the view goes the other way
+                let direction = match key {
+                    KeyCode::ArrowLeft | KeyCode::KeyH => cgmath::Vector2::new(1.0, 0.0),
+                    KeyCode::ArrowRight | KeyCode::KeyL => cgmath::Vector2::new(-1.0, 0.0),
+                    KeyCode::ArrowUp | KeyCode::KeyK => cgmath::Vector2::new(0.0, 1.0),
+                    _ => cgmath::Vector2::new(0.0, -1.0),
+                };
+
+                let step = match self.shift_key_held {
+                    true => KEY_PAN_STEP_LARGE,
+                    false => KEY_PAN_STEP,
+                };
+
+                let window = self.size.width.min(self.size.height) as f32;
+                self.camera.animate_pan(direction * window * step);
+            }
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::Digit0 | KeyCode::Numpad0),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.camera.animate_zoom(1.0),
 
             WindowEvent::KeyboardInput {
                 event:
@@ -391,16 +877,203 @@ impl<'a> State<'a> {
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
-                        physical_key: PhysicalKey::Code(KeyCode::KeyR),
+                        physical_key: PhysicalKey::Code(KeyCode::KeyC),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.set_cursor_visible(!self.show_cursor),
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyS),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                self.uniform.selecting = 1;
+                self.window
+                    .set_cursor_icon(winit::window::CursorIcon::Crosshair);
+            }
+
+            // leave selection mode instead of quitting
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::Escape),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } if self.uniform.selecting == 1 => {
+                self.uniform.selecting = 0;
+                self.selection_start = None;
+                self.window
+                    .set_cursor_icon(winit::window::CursorIcon::Default);
+            }
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key:
+                            PhysicalKey::Code(key @ (KeyCode::BracketLeft | KeyCode::BracketRight)),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                let step = match key {
+                    KeyCode::BracketLeft => -0.1,
+                    _ => 0.1,
+                };
+
+                self.uniform.flashlight_dim = (self.uniform.flashlight_dim + step).clamp(0.0, 1.0);
+            }
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyE),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                self.uniform.flashlight_softness =
+                    Self::cycle(FLASHLIGHT_SOFTNESS, self.uniform.flashlight_softness)
+            }
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyB),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                let [r, g, b, a] = self.uniform.flashlight_color;
+                let [r, g, b] = Self::cycle(FLASHLIGHT_COLORS, [r, g, b]);
+
+                self.uniform.flashlight_color = [r, g, b, a];
+            }
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyZ),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                self.camera.anchor = match self.camera.anchor {
+                    ZoomAnchor::Cursor => ZoomAnchor::Center,
+                    ZoomAnchor::Center => ZoomAnchor::Cursor,
+                }
+            }
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyM),
+                        state: ElementState::Pressed,
                         ..
                     },
                 ..
             } => {
-                self.camera_velocity = 0.0;
-                self.camera_target = cgmath::Vector2::zero();
-                self.camera_zoom = 1.0;
+                self.uniform.loupe = (self.uniform.loupe == 0) as _;
+
+                // back to 1:1 around the middle of the window, the lens does the zooming
+                if self.uniform.loupe == 1 {
+                    self.camera.animate_zoom(1.0);
+                }
             }
 
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyI),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.set_flashlight_space(match self.flashlight_space() {
+                FlashlightSpace::Window => FlashlightSpace::Image,
+                FlashlightSpace::Image => FlashlightSpace::Window,
+            }),
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyO),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.uniform.flashlight_shape = self.flashlight_shape().next() as _,
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyP),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.picker = !self.picker,
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyY),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.copy_color(),
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyG),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.uniform.grid = (self.uniform.grid == 0) as _,
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyN),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.sampling = self.sampling.next(),
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyD),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.start_countdown(),
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyR),
+                        ..
+                    },
+                ..
+            } => self.camera.reset(),
+
             _ => return false,
         }
 
@@ -408,10 +1081,54 @@ impl<'a> State<'a> {
     }
 
     fn update(&mut self) {
+        if let Some(live) = &self.live {
+            let patches: Vec<_> = live.patches().collect();
+            let cursor = live.cursor();
+
+            if !patches.is_empty() || cursor.is_some() {
+                // take the old cursor out first, a patch may only cover part of it
+                if let Some(old) = self.cursor.as_ref().filter(|_| self.show_cursor) {
+                    self.texture.write(
+                        &self.queue,
+                        old.origin,
+                        old.dimensions,
+                        &old.without_cursor,
+                    );
+                }
+
+                for patch in &patches {
+                    let Rect {
+                        x,
+                        y,
+                        width,
+                        height,
+                    } = patch.rect;
+
+                    self.texture
+                        .write(&self.queue, (x as _, y as _), (width, height), &patch.data);
+                    self.frame
+                        .write((x as _, y as _), (width, height), &patch.data);
+                }
+
+                if cursor.is_some() {
+                    self.frame.cursor = cursor;
+                }
+
+                self.cursor = self.frame.cursor_patch();
+
+                if let Some(new) = self.cursor.as_ref().filter(|_| self.show_cursor) {
+                    self.texture
+                        .write(&self.queue, new.origin, new.dimensions, &new.with_cursor);
+                }
+
+                self.texture.generate_mipmaps(&self.device, &self.queue);
+            }
+        }
+
         if let Some(start_pos) = self.click_start_position {
             let displacement = self.last_mouse_position - start_pos;
 
-            self.camera_target += displacement;
+            self.camera.pan(displacement);
 
             let PhysicalSize { width, height } = self.window.inner_size();
             let sw = width as f32;
@@ -439,31 +1156,114 @@ impl<'a> State<'a> {
                 .unwrap();
         }
 
-        self.flashlight_radius_velocity *= 0.9;
-        self.camera_velocity *= 0.95;
+        // everything moves by how much time passed, not by frame, so it feels the same at
+        // any refresh rate
+        let now = Instant::now();
+        let dt = (now - self.last_update).as_secs_f32().min(MAX_FRAME_TIME);
+        self.last_update = now;
+
+        self.uniform.loupe_radius += glide(&mut self.loupe_radius_velocity, RADIUS_FRICTION, dt);
+        self.uniform.loupe_radius = self.uniform.loupe_radius.clamp(30.0, 1000.0);
+        self.uniform.loupe_magnification +=
+            glide(&mut self.loupe_magnification_velocity, RADIUS_FRICTION, dt);
+        self.uniform.loupe_magnification = self.uniform.loupe_magnification.clamp(1.5, 32.0);
+
+        // the image space light goes under the mouse pointer as it was on the last frame,
+        // from then on it stays on that spot of the image however the camera moves
+        if self.flashlight_anchor.is_none() {
+            self.flashlight_anchor = Some(self.camera.window_to_world(self.last_mouse_position));
+        }
 
-        self.uniform.flashlight_radius += self.flashlight_radius_velocity;
-        self.uniform.flashlight_radius = self.uniform.flashlight_radius.clamp(30.0, 1000.0);
-        self.camera_zoom += self.camera_velocity;
-        self.camera_zoom = self.camera_zoom.clamp(0.01, 100.0);
+        let radius_change = glide(&mut self.flashlight_radius_velocity, RADIUS_FRICTION, dt);
 
-        let s = self.window.inner_size();
-        let sw = s.width as f32;
-        let sh = s.height as f32;
+        match self.flashlight_space() {
+            FlashlightSpace::Window => {
+                self.uniform.flashlight_radius += radius_change;
+                self.uniform.flashlight_radius = self.uniform.flashlight_radius.clamp(30.0, 1000.0);
+            }
 
-        let center_x = sw / 2.0;
-        let center_y = sh / 2.0;
+            // same speed on screen, down to a single pixel and up to the whole image
+            FlashlightSpace::Image => {
+                let (width, height) = self.texture.dimensions;
 
-        let z = self.camera_zoom;
-        let o = self.camera_target;
+                self.uniform.flashlight_radius += radius_change / self.camera.zoom();
+                self.uniform.flashlight_radius = self
+                    .uniform
+                    .flashlight_radius
+                    .clamp(1.0, width.max(height).max(1) as f32);
+            }
+        }
 
-        let left = center_x - (center_x / z) - o.x / z;
-        let right = center_x + (center_x / z) - o.x / z;
-        let bottom = center_y - (center_y / z) + o.y / z;
-        let top = center_y + (center_y / z) + o.y / z;
+        self.camera.update(dt, self.last_mouse_position);
 
-        self.uniform.projection_matrix = cgmath::ortho(left, right, bottom, top, -1.0, 1.0).into();
+        self.uniform.projection_matrix = self.camera.projection().into();
         self.uniform.mouse_position = self.last_mouse_position.into();
+        if let Some(anchor) = self.flashlight_anchor {
+            self.uniform.flashlight_anchor = anchor.into();
+        }
+        self.uniform.zoom = self.camera.zoom();
+        self.uniform.image_size = [
+            self.texture.dimensions.0 as _,
+            self.texture.dimensions.1 as _,
+        ];
+
+        self.uniform.selection = match self.selection_start {
+            Some(start) => {
+                let end = self.last_mouse_position;
+                [
+                    start.x.min(end.x),
+                    start.y.min(end.y),
+                    start.x.max(end.x),
+                    start.y.max(end.y),
+                ]
+            }
+
+            None => [0.0; 4],
+        };
+
+        // the colour picker, not while dragging out a selection
+        let picked = self
+            .camera
+            .window_to_image(self.last_mouse_position)
+            .filter(|_| self.picker && self.uniform.selecting == 0)
+            .and_then(|(x, y)| {
+                let [r, g, b] = self.frame.pixel(x, y)?;
+
+                Some((
+                    self.last_mouse_position,
+                    [r, g, b],
+                    [
+                        format!("{x}, {y}"),
+                        format!("#{r:02X}{g:02X}{b:02X}  {r}, {g}, {b}"),
+                    ],
+                ))
+            });
+
+        self.hud
+            .update(&self.queue, self.window.inner_size(), picked);
+
+        // the lens shares the sampler, sharp pixels in it matter more than outside
+        let zoom = match self.uniform.loupe {
+            1 => self.camera.zoom() * self.uniform.loupe_magnification,
+            _ => self.camera.zoom(),
+        };
+
+        let filter = match self.sampling {
+            Sampling::Linear => wgpu::FilterMode::Linear,
+            Sampling::Nearest => wgpu::FilterMode::Nearest,
+            Sampling::Auto if zoom > AUTO_NEAREST_ZOOM => wgpu::FilterMode::Nearest,
+            Sampling::Auto => wgpu::FilterMode::Linear,
+        };
+
+        if filter != self.filter {
+            self.filter = filter;
+            self.texture_bind_groups = create_texture_bind_groups(
+                &self.device,
+                &self.texture_bind_group_layout,
+                &self.texture,
+                filter,
+            );
+        }
 
         self.queue.write_buffer(
             &self.uniform_buffer,
@@ -505,10 +1305,17 @@ impl<'a> State<'a> {
 
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-        render_pass.set_bind_group(1, &self.texture_bind_group, &[]);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.draw_indexed(0..INDICES.len() as _, 0, 0..1);
+
+        for (i, bind_group) in self.texture_bind_groups.iter().enumerate() {
+            let first = (i * INDICES.len()) as u32;
+
+            render_pass.set_bind_group(1, bind_group, &[]);
+            render_pass.draw_indexed(first..first + INDICES.len() as u32, 0, 0..1);
+        }
+
+        self.hud.draw(&mut render_pass);
 
         drop(render_pass);
 
@@ -519,22 +1326,225 @@ impl<'a> State<'a> {
     }
 }
 
+/// What `+`, `-` and `=` do.
+#[derive(Debug, PartialEq)]
+enum SymbolKey {
+    /// zoom in or out by this factor
+    Zoom(f32),
+    Fit,
+}
+
+/// Picks the action for a key that typed `typed`, or `unmodified` without any modifiers
+/// held. Shift zooms further, unless it's needed to type the key at all, like `+` on US
+/// layouts (the numpad's works there).
+fn symbol_key(typed: &Key, unmodified: &Key, shift: bool) -> Option<SymbolKey> {
+    let typed = typed.to_text().unwrap_or_default();
+    let unmodified = unmodified.to_text().unwrap_or_default();
+
+    let step = |symbol: &str| match shift && unmodified == symbol {
+        true => KEY_ZOOM_STEP_LARGE,
+        false => KEY_ZOOM_STEP,
+    };
+
+    if typed == "+" || unmodified == "+" {
+        Some(SymbolKey::Zoom(step("+")))
+    } else if typed == "-" || unmodified == "-" {
+        Some(SymbolKey::Zoom(1.0 / step("-")))
+    } else if typed == "=" {
+        Some(SymbolKey::Fit)
+    } else {
+        None
+    }
+}
+
+/// How far two fingers moved on a touchpad, in pixels, or `None` for a scroll wheel.
+///
+/// Wayland and macOS report touchpads in pixels. X11 reports everything in lines, where a
+/// scroll wheel moves whole lines and a touchpad fractions of one, so high resolution
+/// scroll wheels count as touchpads there.
+fn touchpad_delta(delta: MouseScrollDelta) -> Option<cgmath::Vector2<f32>> {
+    match delta {
+        MouseScrollDelta::PixelDelta(PhysicalPosition { x, y }) => {
+            Some(cgmath::Vector2::new(x as _, y as _))
+        }
+        MouseScrollDelta::LineDelta(x, y) if x.fract() != 0.0 || y.fract() != 0.0 => {
+            Some(cgmath::Vector2::new(x, y) * PIXELS_PER_LINE)
+        }
+        MouseScrollDelta::LineDelta(..) => None,
+    }
+}
+
+/// Binds each tile of `texture` for the fragment shader.
+fn create_texture_bind_groups(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &Texture,
+    filter: wgpu::FilterMode,
+) -> Vec<wgpu::BindGroup> {
+    texture
+        .tiles
+        .iter()
+        .map(|tile| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("texture_bind_group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&tile.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(texture.sampler(filter)),
+                    },
+                ],
+            })
+        })
+        .collect()
+}
+
+/// Vertex and index buffers with one quad per tile, together covering `texture.dimensions`
+/// with the bottom left corner at the origin.
+fn create_quads(device: &wgpu::Device, texture: &Texture) -> (wgpu::Buffer, wgpu::Buffer) {
+    let image_height = texture.dimensions.1 as f32;
+
+    let mut vertices = Vec::with_capacity(texture.tiles.len() * 4);
+    let mut indices = Vec::with_capacity(texture.tiles.len() * INDICES.len());
+
+    for (i, tile) in texture.tiles.iter().enumerate() {
+        // tiles are counted from the top, world y goes up
+        let left = tile.origin.0 as f32;
+        let right = left + tile.size.0 as f32;
+        let top = image_height - tile.origin.1 as f32;
+        let bottom = top - tile.size.1 as f32;
+
+        let ([u0, v0], [u1, v1]) = tile.tex_coords();
+
+        #[rustfmt::skip]
+        vertices.extend_from_slice(&[
+            // the pixel data starts with the top row
+            Vertex { position: [ left, bottom, 0.0], tex_coords: [u0, v1] }, // bottom left
+            Vertex { position: [right, bottom, 0.0], tex_coords: [u1, v1] }, // bottom right
+            Vertex { position: [right,    top, 0.0], tex_coords: [u1, v0] }, // top right
+            Vertex { position: [ left,    top, 0.0], tex_coords: [u0, v0] }, // top left
+        ]);
+
+        indices.extend(INDICES.iter().map(|index| index + 4 * i as u16));
+    }
+
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Vertex Buffer"),
+        contents: bytemuck::cast_slice(&vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Index Buffer"),
+        contents: bytemuck::cast_slice(&indices),
+        usage: wgpu::BufferUsages::INDEX,
+    });
+
+    (vertex_buffer, index_buffer)
+}
+
 pub async fn run() {
-    let screenshot = screenshot();
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("[Error] {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut source: Box<dyn CaptureSource> = match args.input {
+        Input::Screen(target) => Box::new(X11Source { target }),
+        Input::File(path) => Box::new(FileSource { path }),
+        Input::Stdin => Box::new(StdinSource),
+        Input::TestPattern => Box::new(TestPattern::default()),
+    };
+
+    if let Some(delay) = args.delay {
+        std::thread::sleep(delay);
+    }
+
+    let frame = match source.capture() {
+        Ok(frame) => frame,
+        Err(e) => {
+            eprintln!("[Error] {e}");
+            std::process::exit(1);
+        }
+    };
 
     let event_loop = EventLoop::new().unwrap();
-    let window = WindowBuilder::new()
-        .with_fullscreen(Some(winit::window::Fullscreen::Borderless(
-            event_loop.primary_monitor(),
-        )))
-        .build(&event_loop)
-        .unwrap();
 
-    let mut state = State::new(&window, screenshot).await;
+    // the monitor we just captured, or the one most of the captured window is on
+    let captured = frame
+        .position
+        .and_then(|(x, y)| {
+            let x = x + frame.dimensions.0 as i32 / 2;
+            let y = y + frame.dimensions.1 as i32 / 2;
+
+            event_loop.available_monitors().find(|m| {
+                let PhysicalPosition { x: left, y: top } = m.position();
+                let PhysicalSize { width, height } = m.size();
+
+                (left..left + width as i32).contains(&x) && (top..top + height as i32).contains(&y)
+            })
+        })
+        .or_else(|| event_loop.primary_monitor());
+
+    let window = if args.live {
+        // cover as little of what we're capturing as possible: go fullscreen on another
+        // monitor if there is one, otherwise open a regular window
+        let other = event_loop
+            .available_monitors()
+            .find(|m| Some(m) != captured.as_ref());
+
+        match other {
+            Some(monitor) => WindowBuilder::new()
+                .with_fullscreen(Some(winit::window::Fullscreen::Borderless(Some(monitor)))),
+
+            None => WindowBuilder::new().with_inner_size(PhysicalSize::new(
+                frame.dimensions.0 / 2,
+                frame.dimensions.1 / 2,
+            )),
+        }
+    } else {
+        WindowBuilder::new().with_fullscreen(Some(winit::window::Fullscreen::Borderless(captured)))
+    }
+    .with_title("monocle")
+    .build(&event_loop)
+    .unwrap();
+
+    let mut state = State::new(&window, source, frame, args.select).await;
+    state.capture_delay = args.delay.unwrap_or(DEFAULT_CAPTURE_DELAY);
+    state.uniform.grid_zoom = args.grid_zoom;
+    state.uniform.flashlight_dim = args.flashlight_dim;
+    state.uniform.flashlight_softness = args.flashlight_softness;
+    state.uniform.flashlight_shape = args.flashlight_shape as _;
+    state.set_flashlight_space(args.flashlight_space);
+    state.camera.anchor = args.zoom_anchor;
+    state.camera.acceleration = args.acceleration;
+    state.camera.friction = args.friction;
+    state.touchpad_scroll = args.touchpad_scroll;
+
+    let [r, g, b] = args.flashlight_color;
+    state.uniform.flashlight_color = [r, g, b, 1.0];
+    state.set_cursor_visible(args.cursor);
+
+    if args.live {
+        state.live_interval = Some(args.interval);
+        state.start_live_capture();
+    }
+
+    if args.select {
+        window.set_cursor_icon(winit::window::CursorIcon::Crosshair);
+    }
 
     event_loop
         .run(move |event, control_flow| {
-            #[allow(clippy::single_match)]
+            // input gets the first look at window events, the rest fall through
+            #[allow(clippy::collapsible_match)]
             match event {
                 Event::WindowEvent {
                     window_id,
@@ -543,6 +1553,7 @@ pub async fn run() {
                     if !state.input(event) {
                         match event {
                             WindowEvent::Resized(physical_size) => state.resize(*physical_size),
+                            WindowEvent::Moved(_) => state.exclude_window_from_capture(),
                             WindowEvent::CloseRequested
                             | WindowEvent::KeyboardInput {
                                 event:
@@ -582,8 +1593,88 @@ pub async fn run() {
                     }
                 }
 
+                Event::AboutToWait => match state.poll_countdown() {
+                    Some(at) => control_flow.set_control_flow(ControlFlow::WaitUntil(at)),
+                    None => control_flow.set_control_flow(ControlFlow::Wait),
+                },
+
                 _ => {}
             }
         })
         .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(character: &str) -> Key {
+        Key::Character(character.into())
+    }
+
+    #[test]
+    fn shift_zooms_further_both_ways() {
+        // German layout, `+` has its own key and Shift turns it into `*`
+        assert_eq!(
+            symbol_key(&key("+"), &key("+"), false),
+            Some(SymbolKey::Zoom(KEY_ZOOM_STEP))
+        );
+        assert_eq!(
+            symbol_key(&key("*"), &key("+"), true),
+            Some(SymbolKey::Zoom(KEY_ZOOM_STEP_LARGE))
+        );
+        assert_eq!(
+            symbol_key(&key("_"), &key("-"), true),
+            Some(SymbolKey::Zoom(1.0 / KEY_ZOOM_STEP_LARGE))
+        );
+    }
+
+    #[test]
+    fn plus_that_needs_shift_zooms_normally() {
+        // US layout, `+` is a shifted `=`
+        assert_eq!(
+            symbol_key(&key("+"), &key("="), true),
+            Some(SymbolKey::Zoom(KEY_ZOOM_STEP))
+        );
+        assert_eq!(
+            symbol_key(&key("="), &key("="), false),
+            Some(SymbolKey::Fit)
+        );
+    }
+
+    #[test]
+    fn other_keys_are_not_symbols() {
+        assert_eq!(symbol_key(&key("f"), &key("f"), false), None);
+        assert_eq!(
+            symbol_key(
+                &Key::Named(winit::keyboard::NamedKey::Enter),
+                &Key::Named(winit::keyboard::NamedKey::Enter),
+                true
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn pixel_scrolling_is_a_touchpad() {
+        let delta = MouseScrollDelta::PixelDelta(PhysicalPosition::new(3.0, -4.5));
+
+        assert_eq!(touchpad_delta(delta), Some(cgmath::Vector2::new(3.0, -4.5)));
+    }
+
+    #[test]
+    fn fractional_lines_are_a_touchpad() {
+        let delta = MouseScrollDelta::LineDelta(0.0, -0.5);
+
+        assert_eq!(
+            touchpad_delta(delta),
+            Some(cgmath::Vector2::new(0.0, -0.5 * PIXELS_PER_LINE))
+        );
+    }
+
+    #[test]
+    fn whole_lines_are_a_scroll_wheel() {
+        assert_eq!(touchpad_delta(MouseScrollDelta::LineDelta(0.0, 1.0)), None);
+        assert_eq!(touchpad_delta(MouseScrollDelta::LineDelta(-2.0, 0.0)), None);
+    }
+}
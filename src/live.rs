@@ -0,0 +1,433 @@
+use std::os::raw::{c_int, c_ulong};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use x11::xlib;
+
+use crate::capture::{Cursor, PixelFormat};
+use crate::screenshot::{cursor, get_image, window_position, Connection};
+
+// from X11/extensions/Xdamage.h
+const XDAMAGE_NOTIFY: c_int = 0;
+const XDAMAGE_REPORT_RAW_RECTANGLES: c_int = 0;
+
+/// More dirty rectangles than this per tick get merged into their bounding box.
+const MAX_RECTS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+
+    fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+
+    fn from_edges(left: i32, top: i32, right: i32, bottom: i32) -> Option<Self> {
+        (right > left && bottom > top).then(|| Self {
+            x: left,
+            y: top,
+            width: (right - left) as _,
+            height: (bottom - top) as _,
+        })
+    }
+
+    fn intersect(&self, other: &Self) -> Option<Self> {
+        Self::from_edges(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.right().min(other.right()),
+            self.bottom().min(other.bottom()),
+        )
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        Self::from_edges(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.right().max(other.right()),
+            self.bottom().max(other.bottom()),
+        )
+        .unwrap_or(*self)
+    }
+
+    /// The parts of `self` not covered by `other`, as up to four rectangles.
+    fn subtract(&self, other: &Self) -> Vec<Self> {
+        let Some(hole) = self.intersect(other) else {
+            return vec![*self];
+        };
+
+        [
+            // above, below, then left and right of the hole
+            Self::from_edges(self.x, self.y, self.right(), hole.y),
+            Self::from_edges(self.x, hole.bottom(), self.right(), self.bottom()),
+            Self::from_edges(self.x, hole.y, hole.x, hole.bottom()),
+            Self::from_edges(hole.right(), hole.y, self.right(), hole.bottom()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+/// Fresh pixels for part of the captured area.
+pub struct Patch {
+    /// relative to the top left corner of the captured area
+    pub rect: Rect,
+    /// tightly packed, top row first, in the same format as the first capture
+    pub data: Vec<u8>,
+}
+
+/// Keeps re-capturing an area of the screen on a background thread.
+///
+/// Uses XDamage to only read back what changed when it's available, otherwise the whole
+/// area is re-read every `interval`. The thread stops when this is dropped.
+pub struct LiveCapture {
+    patches: mpsc::Receiver<Patch>,
+    shared: Arc<Shared>,
+}
+
+/// What the capture thread and `LiveCapture` both get at.
+#[derive(Default)]
+struct Shared {
+    /// the latest cursor, until `LiveCapture::cursor` takes it
+    cursor: Mutex<Option<Cursor>>,
+    exclude: Mutex<Option<Rect>>,
+    /// whether to keep polling the cursor
+    show_cursor: AtomicBool,
+    stop: AtomicBool,
+}
+
+impl LiveCapture {
+    /// `area` is in root window coordinates, `window` and `format` are where the first
+    /// capture read its pixels from and their format.
+    pub fn spawn(
+        area: Rect,
+        window: Option<xlib::Window>,
+        format: PixelFormat,
+        interval: Duration,
+    ) -> Self {
+        let (sender, patches) = mpsc::channel();
+        let shared = Arc::new(Shared::default());
+
+        let thread_shared = shared.clone();
+        std::thread::spawn(move || {
+            capture_loop(area, window, format, interval, &thread_shared, sender)
+        });
+
+        Self { patches, shared }
+    }
+
+    /// Stops updating `rect` (in root window coordinates), used to keep our own window out
+    /// of the capture.
+    pub fn exclude(&self, rect: Option<Rect>) {
+        *self.shared.exclude.lock().unwrap() = rect;
+    }
+
+    /// Patches captured since the last call.
+    pub fn patches(&self) -> impl Iterator<Item = Patch> + '_ {
+        self.patches.try_iter()
+    }
+
+    /// Starts or stops polling the cursor, there's no need while it isn't shown.
+    pub fn set_cursor_visible(&self, visible: bool) {
+        self.shared.show_cursor.store(visible, Ordering::Relaxed);
+    }
+
+    /// The mouse cursor, relative to the top left corner of the captured area, if it moved
+    /// or changed shape since the last call.
+    pub fn cursor(&self) -> Option<Cursor> {
+        self.shared.cursor.lock().unwrap().take()
+    }
+}
+
+impl Drop for LiveCapture {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn capture_loop(
+    area: Rect,
+    window: Option<xlib::Window>,
+    format: PixelFormat,
+    interval: Duration,
+    shared: &Shared,
+    sender: mpsc::Sender<Patch>,
+) {
+    let Ok(connection) = Connection::open() else {
+        eprintln!("[Error] live capture could not connect to the X server");
+        return;
+    };
+
+    let display = connection.0;
+    let root = unsafe { xlib::XDefaultRootWindow(display) };
+    let damage = unsafe { Damage::new(display, root) };
+
+    // read a captured window from the window itself, like the first capture, so whatever is
+    // on top of it stays out
+    let window = window.and_then(|window| {
+        window_position(display, root, window).map(|position| (window, position))
+    });
+
+    if damage.is_none() {
+        eprintln!("[Warning] XDamage is not available, re-capturing every {interval:?}");
+    }
+
+    let mut last_cursor = None;
+
+    while !shared.stop.load(Ordering::Relaxed) {
+        let start = Instant::now();
+
+        let dirty = match &damage {
+            Some(damage) => unsafe { damage.pending(display) },
+            None => vec![area],
+        };
+
+        let exclude = *shared.exclude.lock().unwrap();
+
+        let mut rects: Vec<Rect> = dirty
+            .iter()
+            .filter_map(|rect| rect.intersect(&area))
+            .flat_map(|rect| match exclude {
+                Some(exclude) => rect.subtract(&exclude),
+                None => vec![rect],
+            })
+            .collect();
+
+        if rects.len() > MAX_RECTS {
+            let bounds = rects.iter().skip(1).fold(rects[0], |a, b| a.union(b));
+            rects = match exclude {
+                Some(exclude) => bounds.subtract(&exclude),
+                None => vec![bounds],
+            };
+        }
+
+        for rect in rects {
+            let from_window = window.and_then(|(window, (x, y))| {
+                unsafe {
+                    get_image(
+                        display,
+                        window,
+                        rect.x - x,
+                        rect.y - y,
+                        rect.width,
+                        rect.height,
+                    )
+                }
+                .ok()
+            });
+
+            // e.g. the window moved or went away
+            let image = from_window.or_else(|| {
+                unsafe { get_image(display, root, rect.x, rect.y, rect.width, rect.height) }.ok()
+            });
+
+            let Some((data, _)) = image else {
+                continue;
+            };
+
+            let patch = Patch {
+                rect: Rect {
+                    x: rect.x - area.x,
+                    y: rect.y - area.y,
+                    ..rect
+                },
+                data,
+            };
+
+            // the window is gone
+            if sender.send(patch).is_err() {
+                return;
+            }
+        }
+
+        // moving the cursor doesn't damage anything, so it's polled on every tick while
+        // it's shown
+        if shared.show_cursor.load(Ordering::Relaxed) {
+            let cursor = cursor(display, format).map(|cursor| Cursor {
+                position: (cursor.position.0 - area.x, cursor.position.1 - area.y),
+                ..cursor
+            });
+
+            if cursor.is_some() && cursor != last_cursor {
+                shared.cursor.lock().unwrap().clone_from(&cursor);
+                last_cursor = cursor;
+            }
+        }
+
+        std::thread::sleep(interval.saturating_sub(start.elapsed()));
+    }
+}
+
+#[repr(C)]
+struct XDamageNotifyEvent {
+    kind: c_int,
+    serial: c_ulong,
+    send_event: xlib::Bool,
+    display: *mut xlib::Display,
+    drawable: xlib::Drawable,
+    damage: xlib::XID,
+    level: c_int,
+    more: xlib::Bool,
+    timestamp: xlib::Time,
+    area: xlib::XRectangle,
+    geometry: xlib::XRectangle,
+}
+
+type QueryExtension =
+    unsafe extern "C" fn(*mut xlib::Display, *mut c_int, *mut c_int) -> xlib::Bool;
+type Create = unsafe extern "C" fn(*mut xlib::Display, xlib::Drawable, c_int) -> xlib::XID;
+
+/// A damage object on the root window. libXdamage is loaded at runtime, neither `x11` nor
+/// `x11-dl` have bindings for it.
+struct Damage {
+    _library: libloading::Library,
+    event_base: c_int,
+}
+
+impl Damage {
+    unsafe fn new(display: *mut xlib::Display, root: xlib::Window) -> Option<Self> {
+        let library = libloading::Library::new("libXdamage.so.1").ok()?;
+
+        let query_extension = *library
+            .get::<QueryExtension>(b"XDamageQueryExtension\0")
+            .ok()?;
+        let create = *library.get::<Create>(b"XDamageCreate\0").ok()?;
+
+        let (mut event_base, mut error_base) = (0, 0);
+        if query_extension(display, &mut event_base, &mut error_base) == xlib::False {
+            return None;
+        }
+
+        // lives until the connection is closed
+        create(display, root, XDAMAGE_REPORT_RAW_RECTANGLES);
+
+        Some(Self {
+            _library: library,
+            event_base,
+        })
+    }
+
+    /// Drains the damage events received so far.
+    unsafe fn pending(&self, display: *mut xlib::Display) -> Vec<Rect> {
+        let mut rects = Vec::new();
+        let mut event: xlib::XEvent = std::mem::zeroed();
+
+        while xlib::XPending(display) > 0 {
+            xlib::XNextEvent(display, &mut event);
+
+            if event.get_type() == self.event_base + XDAMAGE_NOTIFY {
+                let event = &*(&event as *const xlib::XEvent as *const XDamageNotifyEvent);
+                let area = event.area;
+
+                rects.push(Rect {
+                    x: area.x as _,
+                    y: area.y as _,
+                    width: area.width as _,
+                    height: area.height as _,
+                });
+            }
+        }
+
+        rects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn separate_rects_dont_intersect() {
+        let a = rect(0, 0, 10, 10);
+
+        assert_eq!(a.intersect(&rect(20, 0, 10, 10)), None);
+        // touching edges share no pixels
+        assert_eq!(a.intersect(&rect(10, 0, 10, 10)), None);
+        assert_eq!(a.intersect(&rect(0, 10, 10, 10)), None);
+    }
+
+    #[test]
+    fn overlapping_edges_intersect() {
+        let a = rect(0, 0, 10, 10);
+
+        assert_eq!(a.intersect(&rect(8, -5, 10, 10)), Some(rect(8, 0, 2, 5)));
+        assert_eq!(a.intersect(&rect(2, 2, 3, 3)), Some(rect(2, 2, 3, 3)));
+    }
+
+    #[test]
+    fn union_is_the_bounding_box() {
+        assert_eq!(
+            rect(0, 0, 2, 2).union(&rect(5, -3, 1, 1)),
+            rect(0, -3, 6, 5)
+        );
+        assert_eq!(rect(0, 0, 4, 4).union(&rect(1, 1, 1, 1)), rect(0, 0, 4, 4));
+    }
+
+    #[test]
+    fn subtracting_something_apart_changes_nothing() {
+        let a = rect(0, 0, 10, 10);
+
+        assert_eq!(a.subtract(&rect(10, 0, 5, 5)), [a]);
+    }
+
+    #[test]
+    fn subtracting_a_cover_leaves_nothing() {
+        let a = rect(2, 2, 4, 4);
+
+        assert_eq!(a.subtract(&a), []);
+        assert_eq!(a.subtract(&rect(0, 0, 10, 10)), []);
+    }
+
+    #[test]
+    fn subtracting_an_edge_leaves_the_rest() {
+        let a = rect(0, 0, 10, 10);
+
+        // the right 3 columns
+        assert_eq!(a.subtract(&rect(7, -1, 5, 12)), [rect(0, 0, 7, 10)]);
+        // the bottom right corner
+        assert_eq!(
+            a.subtract(&rect(6, 6, 10, 10)),
+            [rect(0, 0, 10, 6), rect(0, 6, 6, 4)]
+        );
+    }
+
+    #[test]
+    fn subtracting_a_hole_leaves_four_pieces() {
+        let a = rect(0, 0, 10, 10);
+        let pieces = a.subtract(&rect(3, 4, 2, 3));
+
+        assert_eq!(
+            pieces,
+            [
+                rect(0, 0, 10, 4),
+                rect(0, 7, 10, 3),
+                rect(0, 4, 3, 3),
+                rect(5, 4, 5, 3),
+            ]
+        );
+
+        // together they cover everything but the hole, once
+        let area: u32 = pieces.iter().map(|r| r.width * r.height).sum();
+        assert_eq!(area, 10 * 10 - 2 * 3);
+    }
+}
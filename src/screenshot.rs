@@ -1,60 +1,1082 @@
-use x11::xlib;
+use std::sync::atomic::{AtomicPtr, AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+use x11::{xlib, xshm};
+use x11_dl::{xfixes, xrandr};
+
+use crate::capture::{CaptureError, CaptureSource, Cursor, Frame, PixelFormat};
+
+// `x11` only links libXext when the `dpms` feature is enabled, but the MIT-SHM
+// functions live there too.
+#[link(name = "Xext")]
+extern "C" {}
+
+const CHANNELS: usize = 4;
+
+// from X11/cursorfont.h
+const XC_CROSSHAIR: u32 = 34;
+
+/// What part of the screen to capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// The monitor the mouse pointer is on.
+    Pointer,
+    /// The n-th monitor, counted from 0 left to right.
+    Monitor(usize),
+    /// The whole X screen, spanning every monitor.
+    All,
+    /// A single window, by X window id.
+    Window(xlib::Window),
+    /// A single window, chosen by clicking on it.
+    PickWindow,
+}
+
+/// A monitor as seen by XRandR, in root window coordinates.
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub primary: bool,
+}
+
+impl Monitor {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x
+            && y >= self.y
+            && x < self.x + self.width as i32
+            && y < self.y + self.height as i32
+    }
+}
+
+/// An Xlib connection, closed on drop.
+pub(crate) struct Connection(pub(crate) *mut xlib::Display);
+
+impl Connection {
+    pub(crate) fn open() -> Result<Self, CaptureError> {
+        let display = unsafe { xlib::XOpenDisplay(std::ptr::null()) };
+
+        if display.is_null() {
+            return Err(CaptureError::NoDisplay);
+        }
+
+        Ok(Self(display))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe { xlib::XCloseDisplay(self.0) };
+    }
+}
+
+/// Captures the X screen.
+pub struct X11Source {
+    pub target: Target,
+}
+
+impl CaptureSource for X11Source {
+    fn capture(&mut self) -> Result<Frame, CaptureError> {
+        // only ask once, later captures (e.g. the countdown) reuse the window
+        if self.target == Target::PickWindow {
+            let connection = Connection::open()?;
+            let root = unsafe { xlib::XDefaultRootWindow(connection.0) };
+
+            self.target = Target::Window(pick_window(connection.0, root)?);
+        }
+
+        screenshot(self.target)
+    }
+}
+
+pub fn screenshot(target: Target) -> Result<Frame, CaptureError> {
+    let connection = Connection::open()?;
+    let display = connection.0;
 
-/// returns: ((width, height), data)
-pub fn screenshot() -> ((u32, u32), Vec<u8>) {
-    let display = unsafe { xlib::XOpenDisplay(std::ptr::null()) };
     let screen = unsafe { xlib::XDefaultScreen(display) };
     let root = unsafe { xlib::XRootWindow(display, screen) };
 
-    let height = unsafe { xlib::XDisplayHeight(display, screen) };
-    let width = unsafe { xlib::XDisplayWidth(display, screen) };
+    let visual = unsafe { xlib::XDefaultVisual(display, screen) };
+    let depth = unsafe { xlib::XDefaultDepth(display, screen) };
+
+    let class = unsafe { (*visual).class };
+    if class != xlib::TrueColor && class != xlib::DirectColor {
+        return Err(CaptureError::UnsupportedVisual { class, depth });
+    }
+
+    let (window, (x, y, width, height)) = match target {
+        Target::All => unsafe {
+            (
+                None,
+                (
+                    0,
+                    0,
+                    xlib::XDisplayWidth(display, screen) as u32,
+                    xlib::XDisplayHeight(display, screen) as u32,
+                ),
+            )
+        },
+
+        Target::Pointer => {
+            let monitors = monitors(display, root);
+            let (px, py) = pointer_position(display, root);
+
+            let monitor = monitors
+                .iter()
+                .find(|m| m.contains(px, py))
+                .or_else(|| monitors.iter().find(|m| m.primary))
+                .unwrap_or(&monitors[0]);
+
+            (None, (monitor.x, monitor.y, monitor.width, monitor.height))
+        }
+
+        Target::Monitor(index) => {
+            let monitors = monitors(display, root);
+            let monitor = monitors.get(index).ok_or(CaptureError::NoSuchMonitor {
+                index,
+                count: monitors.len(),
+            })?;
+
+            (None, (monitor.x, monitor.y, monitor.width, monitor.height))
+        }
+
+        Target::Window(window) => {
+            let (source, area) = window_region(display, root, screen, window)?;
+            (Some(source), area)
+        }
+
+        Target::PickWindow => {
+            let window = pick_window(display, root)?;
+            let (source, area) = window_region(display, root, screen, window)?;
+            (Some(source), area)
+        }
+    };
+
+    // read the window itself so overlapping windows, menus and tooltips stay out of the
+    // capture. Fall back to the root if the server won't hand its contents over.
+    let from_window = window.and_then(|window| {
+        read_area(
+            display,
+            window.drawable,
+            window.visual,
+            window.depth,
+            window.offset,
+            (width, height),
+        )
+        .ok()
+        .map(|image| (image, window.drawable))
+    });
+
+    let ((data, format), window) = match from_window {
+        Some((image, window)) => (image, Some(window)),
+        None => (
+            read_area(display, root, visual, depth, (x, y), (width, height))?,
+            None,
+        ),
+    };
+
+    let cursor = cursor(display, format).map(|cursor| Cursor {
+        position: (cursor.position.0 - x, cursor.position.1 - y),
+        ..cursor
+    });
+
+    Ok(Frame {
+        position: Some((x, y)),
+        window,
+        dimensions: (width, height),
+        format,
+        data,
+        cursor,
+    })
+}
+
+/// Reads a `width` x `height` area at (`x`, `y`) of `drawable`, through MIT-SHM if possible.
+fn read_area(
+    display: *mut xlib::Display,
+    drawable: xlib::Drawable,
+    visual: *mut xlib::Visual,
+    depth: i32,
+    (x, y): (i32, i32),
+    (width, height): (u32, u32),
+) -> Result<(Vec<u8>, PixelFormat), CaptureError> {
+    match unsafe { ShmImage::new(display, visual, depth as _, width, height) } {
+        Some(mut shm) => {
+            if !unsafe { shm.capture(drawable, x, y) } {
+                return Err(CaptureError::GetImage);
+            }
+
+            shm.convert()
+        }
+
+        // no MIT-SHM (e.g. a remote display), go through the regular protocol request
+        None => unsafe { get_image(display, drawable, x, y, width, height) },
+    }
+}
+
+/// The current cursor image from XFixes, positioned in root window coordinates.
+pub(crate) fn cursor(display: *mut xlib::Display, format: PixelFormat) -> Option<Cursor> {
+    // libXfixes is loaded at runtime like the other optional extensions, and only once since
+    // live capture polls the cursor
+    static XFIXES: OnceLock<Option<xfixes::Xlib>> = OnceLock::new();
+    let xfixes = XFIXES.get_or_init(|| xfixes::Xlib::open().ok()).as_ref()?;
+    let display_dl = display as *mut x11_dl::xlib::Display;
+
+    let (mut event_base, mut error_base) = (0, 0);
+    if unsafe { (xfixes.XFixesQueryExtension)(display_dl, &mut event_base, &mut error_base) }
+        == xlib::False
+    {
+        return None;
+    }
+
+    let image = trap_errors(display, || unsafe {
+        (xfixes.XFixesGetCursorImage)(display_dl)
+    })?;
+    if image.is_null() {
+        return None;
+    }
+
+    let cursor = unsafe {
+        let image = &*image;
+        let count = image.width as usize * image.height as usize;
+
+        // premultiplied ARGB, one pixel per `unsigned long` regardless of its size
+        let pixels = std::slice::from_raw_parts(image.pixels, count)
+            .iter()
+            .flat_map(|&argb| {
+                let [b, g, r, a] = (argb as u32).to_le_bytes();
+                match format {
+                    PixelFormat::Rgba8 => [r, g, b, a],
+                    PixelFormat::Bgra8 => [b, g, r, a],
+                }
+            })
+            .collect();
+
+        Cursor {
+            position: (
+                image.x as i32 - image.xhot as i32,
+                image.y as i32 - image.yhot as i32,
+            ),
+            dimensions: (image.width as _, image.height as _),
+            pixels,
+        }
+    };
 
-    let image = unsafe {
+    unsafe { xlib::XFree(image as _) };
+
+    Some(cursor)
+}
+
+/// Reads a rectangle of `drawable` with a plain `XGetImage` request.
+///
+/// # Safety
+/// `display` must be an open connection.
+pub unsafe fn get_image(
+    display: *mut xlib::Display,
+    drawable: xlib::Drawable,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+) -> Result<(Vec<u8>, PixelFormat), CaptureError> {
+    // e.g. BadMatch for a rectangle that isn't inside the drawable
+    let image = trap_errors(display, || unsafe {
         xlib::XGetImage(
             display,
-            root,
-            0,
-            0,
-            width as _,
-            height as _,
+            drawable,
+            x,
+            y,
+            width,
+            height,
             xlib::XAllPlanes(),
             xlib::ZPixmap,
         )
+    })
+    .filter(|image| !image.is_null())
+    .ok_or(CaptureError::GetImage)?;
+
+    let data = unsafe { convert(&*image) };
+    unsafe { xlib::XDestroyImage(image) };
+
+    data
+}
+
+/// Lists the active monitors from left to right.
+///
+/// Falls back to a single monitor covering the whole screen if XRandR is not available.
+fn monitors(display: *mut xlib::Display, root: xlib::Window) -> Vec<Monitor> {
+    let mut monitors = unsafe { randr_monitors(display, root) }.unwrap_or_default();
+
+    if monitors.is_empty() {
+        let screen = unsafe { xlib::XDefaultScreen(display) };
+
+        monitors.push(Monitor {
+            name: String::from("screen"),
+            x: 0,
+            y: 0,
+            width: unsafe { xlib::XDisplayWidth(display, screen) } as _,
+            height: unsafe { xlib::XDisplayHeight(display, screen) } as _,
+            primary: true,
+        });
+    }
+
+    monitors.sort_by_key(|m| (m.x, m.y));
+    monitors
+}
+
+/// Walks the connected outputs and the CRTCs driving them. Mirrored outputs share a CRTC
+/// and are only reported once.
+unsafe fn randr_monitors(display: *mut xlib::Display, root: xlib::Window) -> Option<Vec<Monitor>> {
+    // libXrandr is loaded at runtime, so a missing library just means no multi-monitor support
+    let xrandr = xrandr::Xrandr::open().ok()?;
+    let display = display as *mut x11_dl::xlib::Display;
+
+    let (mut event_base, mut error_base) = (0, 0);
+    if (xrandr.XRRQueryExtension)(display, &mut event_base, &mut error_base) == xlib::False {
+        return None;
+    }
+
+    let resources = (xrandr.XRRGetScreenResourcesCurrent)(display, root);
+    if resources.is_null() {
+        return None;
+    }
+
+    let primary = (xrandr.XRRGetOutputPrimary)(display, root);
+    let outputs = std::slice::from_raw_parts((*resources).outputs, (*resources).noutput as _);
+
+    let mut crtcs = Vec::new();
+    let mut monitors = Vec::new();
+
+    for &output in outputs {
+        let info = (xrandr.XRRGetOutputInfo)(display, resources, output);
+        if info.is_null() {
+            continue;
+        }
+
+        let crtc = (*info).crtc;
+        if (*info).connection as i32 == xrandr::RR_Connected && crtc != 0 && !crtcs.contains(&crtc)
+        {
+            let crtc_info = (xrandr.XRRGetCrtcInfo)(display, resources, crtc);
+
+            if !crtc_info.is_null() {
+                let name =
+                    std::slice::from_raw_parts((*info).name as *const u8, (*info).nameLen as _);
+
+                crtcs.push(crtc);
+                monitors.push(Monitor {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    x: (*crtc_info).x,
+                    y: (*crtc_info).y,
+                    width: (*crtc_info).width,
+                    height: (*crtc_info).height,
+                    primary: output == primary,
+                });
+
+                (xrandr.XRRFreeCrtcInfo)(crtc_info);
+            }
+        }
+
+        (xrandr.XRRFreeOutputInfo)(info);
+    }
+
+    (xrandr.XRRFreeScreenResources)(resources);
+
+    Some(monitors)
+}
+
+/// (x, y, width, height) in root window coordinates.
+type Area = (i32, i32, u32, u32);
+
+/// A window to read pixels from directly.
+struct WindowSource {
+    drawable: xlib::Drawable,
+    visual: *mut xlib::Visual,
+    depth: i32,
+    /// top left corner of the captured area, relative to the window
+    offset: (i32, i32),
+}
+
+/// The on-screen part of `window`, in root window coordinates, and where to find it in
+/// the window itself.
+///
+/// Without a compositor the parts of the window covered by other windows have no
+/// contents, the server may hand back whatever is on screen there instead.
+fn window_region(
+    display: *mut xlib::Display,
+    root: xlib::Window,
+    screen: i32,
+    window: xlib::Window,
+) -> Result<(WindowSource, Area), CaptureError> {
+    let mut attributes: xlib::XWindowAttributes = unsafe { std::mem::zeroed() };
+
+    let ok = trap_errors(display, || unsafe {
+        xlib::XGetWindowAttributes(display, window, &mut attributes)
+    });
+
+    if ok != Some(1) {
+        return Err(CaptureError::NoSuchWindow(window));
+    }
+
+    if attributes.map_state != xlib::IsViewable {
+        return Err(CaptureError::WindowNotViewable(window));
+    }
+
+    let (x, y) =
+        window_position(display, root, window).ok_or(CaptureError::NoSuchWindow(window))?;
+
+    // clip to the screen, parts of the window hanging off the edge have no pixels
+    let (screen_width, screen_height) = unsafe {
+        (
+            xlib::XDisplayWidth(display, screen),
+            xlib::XDisplayHeight(display, screen),
+        )
     };
 
-    assert!(!image.is_null());
+    let left = x.max(0);
+    let top = y.max(0);
+    let right = (x + attributes.width).min(screen_width);
+    let bottom = (y + attributes.height).min(screen_height);
 
-    let visual = unsafe { xlib::XDefaultVisual(display, screen) };
-    let (red_mask, green_mask, blue_mask) = unsafe {
-        let v = *visual;
-        (v.red_mask, v.green_mask, v.blue_mask)
+    if right <= left || bottom <= top {
+        return Err(CaptureError::WindowNotViewable(window));
+    }
+
+    let source = WindowSource {
+        drawable: window,
+        visual: attributes.visual,
+        depth: attributes.depth,
+        offset: (left - x, top - y),
     };
 
-    const CHANNELS: usize = 4;
+    Ok((
+        source,
+        (left, top, (right - left) as _, (bottom - top) as _),
+    ))
+}
 
-    let mut buf: Vec<u8> = vec![0; (width * height) as usize * CHANNELS];
-    for y in 0..height {
-        for x in 0..width {
-            let pixel = unsafe { xlib::XGetPixel(image, x, y) };
+/// The top left corner of `window` in root window coordinates, `None` if it's gone.
+pub(crate) fn window_position(
+    display: *mut xlib::Display,
+    root: xlib::Window,
+    window: xlib::Window,
+) -> Option<(i32, i32)> {
+    let (mut x, mut y, mut child) = (0, 0, 0);
 
-            let rgb: [u8; CHANNELS] = [
-                ((pixel & red_mask) >> red_mask.trailing_zeros()) as _,
-                ((pixel & green_mask) >> green_mask.trailing_zeros()) as _,
-                ((pixel & blue_mask) >> blue_mask.trailing_zeros()) as _,
-                255,
-            ];
+    trap_errors(display, || unsafe {
+        xlib::XTranslateCoordinates(display, window, root, 0, 0, &mut x, &mut y, &mut child)
+    })?;
 
-            // Calculate the index for the flipped image
-            let index = ((height - 1 - y) * width + x) as usize * CHANNELS;
-            // let index = (y * width + x) as usize * CHANNELS;
-            buf[index..index + CHANNELS].copy_from_slice(&rgb);
+    Some((x, y))
+}
+
+/// Grabs the pointer with a crosshair cursor and waits for a click, like `xwininfo`.
+/// Right click cancels.
+fn pick_window(
+    display: *mut xlib::Display,
+    root: xlib::Window,
+) -> Result<xlib::Window, CaptureError> {
+    let cursor = unsafe { xlib::XCreateFontCursor(display, XC_CROSSHAIR) };
+
+    let grab = unsafe {
+        xlib::XGrabPointer(
+            display,
+            root,
+            xlib::False,
+            xlib::ButtonPressMask as _,
+            xlib::GrabModeAsync,
+            xlib::GrabModeAsync,
+            root,
+            cursor,
+            xlib::CurrentTime,
+        )
+    };
+
+    if grab != xlib::GrabSuccess {
+        unsafe { xlib::XFreeCursor(display, cursor) };
+        return Err(CaptureError::GrabPointer);
+    }
+
+    let mut event: xlib::XEvent = unsafe { std::mem::zeroed() };
+    let button = loop {
+        unsafe { xlib::XNextEvent(display, &mut event) };
+
+        if event.get_type() == xlib::ButtonPress {
+            break xlib::XButtonEvent::from(event);
         }
+    };
+
+    unsafe {
+        xlib::XUngrabPointer(display, xlib::CurrentTime);
+        xlib::XFreeCursor(display, cursor);
+    }
+
+    if button.button == xlib::Button3 {
+        return Err(CaptureError::Cancelled);
     }
 
+    let window = window_at(display, root, button.x_root, button.y_root).unwrap_or(root);
+    Ok(client_window(display, window).unwrap_or(window))
+}
+
+/// Walks the children of `parent` from the top of the stacking order down and returns the
+/// first viewable one under (`x`, `y`), which is usually a window manager frame.
+fn window_at(
+    display: *mut xlib::Display,
+    parent: xlib::Window,
+    x: i32,
+    y: i32,
+) -> Option<xlib::Window> {
+    children(display, parent).into_iter().rev().find(|&child| {
+        let mut attributes: xlib::XWindowAttributes = unsafe { std::mem::zeroed() };
+
+        trap_errors(display, || unsafe {
+            xlib::XGetWindowAttributes(display, child, &mut attributes)
+        }) == Some(1)
+            && attributes.map_state == xlib::IsViewable
+            && x >= attributes.x
+            && y >= attributes.y
+            && x < attributes.x + attributes.width
+            && y < attributes.y + attributes.height
+    })
+}
+
+/// Finds the application window inside a window manager frame: the first window in the
+/// tree below `window` that has `WM_STATE` set.
+fn client_window(display: *mut xlib::Display, window: xlib::Window) -> Option<xlib::Window> {
+    let wm_state = unsafe { xlib::XInternAtom(display, c"WM_STATE".as_ptr(), xlib::True) };
+    if wm_state == 0 {
+        return None;
+    }
+
+    let has_wm_state = |window| {
+        let (mut actual_type, mut actual_format) = (0, 0);
+        let (mut items, mut bytes_after) = (0, 0);
+        let mut data = std::ptr::null_mut();
+
+        unsafe {
+            xlib::XGetWindowProperty(
+                display,
+                window,
+                wm_state,
+                0,
+                0,
+                xlib::False,
+                xlib::AnyPropertyType as _,
+                &mut actual_type,
+                &mut actual_format,
+                &mut items,
+                &mut bytes_after,
+                &mut data,
+            );
+
+            if !data.is_null() {
+                xlib::XFree(data as _);
+            }
+        }
+
+        actual_type != 0
+    };
+
+    // breadth first, clients are usually one or two levels below the frame
+    let mut queue = std::collections::VecDeque::from([window]);
+    while let Some(window) = queue.pop_front() {
+        if has_wm_state(window) {
+            return Some(window);
+        }
+
+        queue.extend(children(display, window));
+    }
+
+    None
+}
+
+/// The children of `window`, bottom of the stacking order first.
+fn children(display: *mut xlib::Display, window: xlib::Window) -> Vec<xlib::Window> {
+    let (mut root, mut parent) = (0, 0);
+    let mut children = std::ptr::null_mut();
+    let mut count = 0;
+
+    let ok = trap_errors(display, || unsafe {
+        xlib::XQueryTree(
+            display,
+            window,
+            &mut root,
+            &mut parent,
+            &mut children,
+            &mut count,
+        )
+    });
+
+    if ok.unwrap_or(0) == 0 || children.is_null() {
+        return Vec::new();
+    }
+
+    let list = unsafe { std::slice::from_raw_parts(children, count as _) }.to_vec();
+    unsafe { xlib::XFree(children as _) };
+
+    list
+}
+
+type ErrorHandler = unsafe extern "C" fn(*mut xlib::Display, *mut xlib::XErrorEvent) -> i32;
+
+// the error handler is process wide, but Xlib is used from several threads (winit, live
+// capture), so only one trap can be set at a time and it only catches its own display's
+// errors
+static TRAP: Mutex<()> = Mutex::new(());
+static TRAPPED_DISPLAY: AtomicPtr<xlib::Display> = AtomicPtr::new(std::ptr::null_mut());
+static PREVIOUS_HANDLER: Mutex<Option<ErrorHandler>> = Mutex::new(None);
+static X_ERROR: AtomicU8 = AtomicU8::new(0);
+
+unsafe extern "C" fn record_error(
+    display: *mut xlib::Display,
+    event: *mut xlib::XErrorEvent,
+) -> i32 {
+    if display == TRAPPED_DISPLAY.load(Ordering::Relaxed) {
+        X_ERROR.store((*event).error_code, Ordering::Relaxed);
+        return 0;
+    }
+
+    // someone else's connection, hand it to whoever was handling errors before
+    let previous = *PREVIOUS_HANDLER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+
+    match previous {
+        Some(handler) => handler(display, event),
+        None => 0,
+    }
+}
+
+/// Runs `f` with an error handler that records X errors instead of exiting the process,
+/// which is what Xlib does by default. Returns `None` if `f` caused an error, e.g. because
+/// a window went away in the meantime.
+fn trap_errors<T>(display: *mut xlib::Display, f: impl FnOnce() -> T) -> Option<T> {
+    let _trap = TRAP.lock().unwrap_or_else(PoisonError::into_inner);
+
+    unsafe {
+        xlib::XSync(display, xlib::False);
+        X_ERROR.store(0, Ordering::Relaxed);
+        TRAPPED_DISPLAY.store(display, Ordering::Relaxed);
+
+        // held while swapping so other threads' errors don't see a stale previous handler
+        let mut handler = PREVIOUS_HANDLER
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let previous = xlib::XSetErrorHandler(Some(record_error));
+        *handler = previous;
+        drop(handler);
+
+        let result = f();
+        xlib::XSync(display, xlib::False);
+
+        xlib::XSetErrorHandler(previous);
+        TRAPPED_DISPLAY.store(std::ptr::null_mut(), Ordering::Relaxed);
+
+        (X_ERROR.load(Ordering::Relaxed) == 0).then_some(result)
+    }
+}
+
+fn pointer_position(display: *mut xlib::Display, root: xlib::Window) -> (i32, i32) {
+    let (mut root_return, mut child_return) = (0, 0);
+    let (mut x, mut y, mut win_x, mut win_y) = (0, 0, 0, 0);
+    let mut mask = 0;
+
     unsafe {
-        xlib::XDestroyImage(image);
-        xlib::XCloseDisplay(display);
+        xlib::XQueryPointer(
+            display,
+            root,
+            &mut root_return,
+            &mut child_return,
+            &mut x,
+            &mut y,
+            &mut win_x,
+            &mut win_y,
+            &mut mask,
+        )
+    };
+
+    (x, y)
+}
+
+/// An `XImage` whose pixel data lives in a SysV shared memory segment, so the X server
+/// writes the screen contents straight into our address space.
+pub struct ShmImage {
+    display: *mut xlib::Display,
+    image: *mut xlib::XImage,
+    info: xshm::XShmSegmentInfo,
+}
+
+impl ShmImage {
+    /// Returns `None` if the server doesn't support MIT-SHM or the segment could not be set up.
+    ///
+    /// # Safety
+    /// `display` must be an open connection that outlives the image.
+    pub unsafe fn new(
+        display: *mut xlib::Display,
+        visual: *mut xlib::Visual,
+        depth: u32,
+        width: u32,
+        height: u32,
+    ) -> Option<Self> {
+        if xshm::XShmQueryExtension(display) == xlib::False {
+            return None;
+        }
+
+        let mut info = xshm::XShmSegmentInfo {
+            shmseg: 0,
+            shmid: -1,
+            shmaddr: std::ptr::null_mut(),
+            readOnly: xlib::False,
+        };
+
+        let image = xshm::XShmCreateImage(
+            display,
+            visual,
+            depth,
+            xlib::ZPixmap,
+            std::ptr::null_mut(),
+            &mut info,
+            width,
+            height,
+        );
+
+        if image.is_null() {
+            return None;
+        }
+
+        let size = (*image).bytes_per_line as usize * (*image).height as usize;
+        info.shmid = libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600);
+        if info.shmid < 0 {
+            xlib::XDestroyImage(image);
+            return None;
+        }
+
+        let addr = libc::shmat(info.shmid, std::ptr::null(), 0);
+        if addr as isize == -1 {
+            libc::shmctl(info.shmid, libc::IPC_RMID, std::ptr::null_mut());
+            xlib::XDestroyImage(image);
+            return None;
+        }
+
+        info.shmaddr = addr as _;
+        (*image).data = info.shmaddr;
+
+        let attached = xshm::XShmAttach(display, &mut info) != xlib::False;
+        xlib::XSync(display, xlib::False);
+
+        // the segment stays alive until both sides detach, this just makes sure it
+        // doesn't leak if we crash
+        libc::shmctl(info.shmid, libc::IPC_RMID, std::ptr::null_mut());
+
+        let shm = Self {
+            display,
+            image,
+            info,
+        };
+
+        attached.then_some(shm)
+    }
+
+    /// Returns `false` if the server could not fill the image, e.g. because the rectangle
+    /// isn't inside `drawable`.
+    ///
+    /// # Safety
+    /// `drawable` must be a drawable on the image's display.
+    pub unsafe fn capture(&mut self, drawable: xlib::Drawable, x: i32, y: i32) -> bool {
+        let status = trap_errors(self.display, || {
+            xshm::XShmGetImage(
+                self.display,
+                drawable,
+                self.image,
+                x,
+                y,
+                xlib::XAllPlanes() as _,
+            )
+        });
+
+        status.is_some_and(|status| status != xlib::False)
     }
 
-    ((width as _, height as _), buf)
+    /// Copies the last captured pixels out of the segment, see [`convert`].
+    pub fn convert(&self) -> Result<(Vec<u8>, PixelFormat), CaptureError> {
+        // the segment is attached and sized for the image until we are dropped
+        unsafe { convert(&*self.image) }
+    }
+}
+
+impl Drop for ShmImage {
+    fn drop(&mut self) {
+        unsafe {
+            xshm::XShmDetach(self.display, &mut self.info);
+            xlib::XSync(self.display, xlib::False);
+
+            // the data is not malloc'd, don't let XDestroyImage free it
+            (*self.image).data = std::ptr::null_mut();
+            xlib::XDestroyImage(self.image);
+
+            libc::shmdt(self.info.shmaddr as _);
+        }
+    }
+}
+
+/// Copies a `ZPixmap` image into tightly packed pixels, top row first.
+///
+/// The usual 32 bit BGRX layout is copied as is (only dropping row padding) and uploaded
+/// as a BGRA texture. Anything else is converted to RGBA: 8, 16, 24 and 32 bits per pixel
+/// in either byte order, with any channel masks as long as each one is a single run of
+/// bits. Channels narrower or wider than 8 bits (e.g. 5-6-5 or 10-10-10) are rescaled to
+/// 8 bits.
+///
+/// # Safety
+/// `image.data` must point to `image.bytes_per_line * image.height` readable bytes.
+pub unsafe fn convert(image: &xlib::XImage) -> Result<(Vec<u8>, PixelFormat), CaptureError> {
+    let width = image.width as usize;
+    let height = image.height as usize;
+    let stride = image.bytes_per_line as usize;
+
+    let unsupported = || CaptureError::UnsupportedPixelFormat {
+        bits_per_pixel: image.bits_per_pixel,
+        masks: [
+            image.red_mask as _,
+            image.green_mask as _,
+            image.blue_mask as _,
+        ],
+    };
+
+    if image.format != xlib::ZPixmap || !matches!(image.bits_per_pixel, 8 | 16 | 24 | 32) {
+        return Err(unsupported());
+    }
+
+    let bytes_per_pixel = image.bits_per_pixel as usize / 8;
+    if stride < width * bytes_per_pixel {
+        return Err(unsupported());
+    }
+
+    let channels = [image.red_mask, image.green_mask, image.blue_mask]
+        .map(|mask| Channel::new(mask as _, image.bits_per_pixel as _));
+    let [Some(red), Some(green), Some(blue)] = channels else {
+        return Err(unsupported());
+    };
+
+    let data = std::slice::from_raw_parts(image.data as *const u8, stride * height);
+
+    // 8 bits per channel in a 32 bit little endian word, which is what pretty much every
+    // X server uses
+    let bgrx = image.bits_per_pixel == 32
+        && image.byte_order == xlib::LSBFirst
+        && image.red_mask == 0xff0000
+        && image.green_mask == 0x00ff00
+        && image.blue_mask == 0x0000ff;
+
+    if bgrx {
+        let mut buf = Vec::with_capacity(width * height * CHANNELS);
+        for row in data.chunks_exact(stride) {
+            buf.extend_from_slice(&row[..width * CHANNELS]);
+        }
+
+        return Ok((buf, PixelFormat::Bgra8));
+    }
+
+    let mut buf: Vec<u8> = vec![0; width * height * CHANNELS];
+    let msb_first = image.byte_order == xlib::MSBFirst;
+
+    for (dst, src) in buf
+        .chunks_exact_mut(width * CHANNELS)
+        .zip(data.chunks_exact(stride))
+    {
+        for (dst, src) in dst
+            .chunks_exact_mut(CHANNELS)
+            .zip(src[..width * bytes_per_pixel].chunks_exact(bytes_per_pixel))
+        {
+            let pixel = match msb_first {
+                true => src.iter().fold(0, |pixel, &byte| pixel << 8 | byte as u64),
+                false => src
+                    .iter()
+                    .rev()
+                    .fold(0, |pixel, &byte| pixel << 8 | byte as u64),
+            };
+
+            dst.copy_from_slice(&[red.get(pixel), green.get(pixel), blue.get(pixel), 255]);
+        }
+    }
+
+    Ok((buf, PixelFormat::Rgba8))
+}
+
+/// Where one colour channel sits in a pixel.
+#[derive(Debug, Clone, Copy)]
+struct Channel {
+    shift: u32,
+    bits: u32,
+}
+
+impl Channel {
+    /// `None` unless `mask` is a single, non-empty run of bits within a pixel.
+    fn new(mask: u64, bits_per_pixel: u32) -> Option<Self> {
+        if mask == 0 {
+            return None;
+        }
+
+        let shift = mask.trailing_zeros();
+        let bits = (mask >> shift).trailing_ones();
+
+        let contiguous = (mask >> shift).checked_shr(bits).unwrap_or(0) == 0;
+        (contiguous && shift + bits <= bits_per_pixel).then_some(Self { shift, bits })
+    }
+
+    /// The channel's value scaled to 8 bits.
+    fn get(self, pixel: u64) -> u8 {
+        let value = (pixel >> self.shift) & ((1 << self.bits) - 1);
+
+        match self.bits {
+            8.. => (value >> (self.bits - 8)) as u8,
+            // round to nearest, so the maximum maps to 255
+            _ => ((value * 255 + ((1 << self.bits) - 1) / 2) / ((1 << self.bits) - 1)) as u8,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A client-side image over `data`, with `masks` as red, green, blue.
+    fn synthetic_image(
+        (width, height): (i32, i32),
+        bits_per_pixel: i32,
+        byte_order: i32,
+        masks: [u64; 3],
+        data: &mut [u8],
+    ) -> xlib::XImage {
+        let mut image: xlib::XImage = unsafe { std::mem::zeroed() };
+        image.width = width;
+        image.height = height;
+        image.format = xlib::ZPixmap;
+        image.data = data.as_mut_ptr() as _;
+        image.byte_order = byte_order;
+        image.bits_per_pixel = bits_per_pixel;
+        image.bytes_per_line = data.len() as i32 / height;
+        image.red_mask = masks[0] as _;
+        image.green_mask = masks[1] as _;
+        image.blue_mask = masks[2] as _;
+
+        image
+    }
+
+    fn rgb(pixels: &[[u8; 3]]) -> (Vec<u8>, PixelFormat) {
+        let data = pixels
+            .iter()
+            .flat_map(|&[r, g, b]| [r, g, b, 255])
+            .collect();
+        (data, PixelFormat::Rgba8)
+    }
+
+    #[test]
+    fn bgrx_is_copied_as_is() {
+        let mut data = [
+            0x03, 0x02, 0x01, 0x00, 0x06, 0x05, 0x04, 0x00, // top row
+            0x09, 0x08, 0x07, 0x00, 0x0c, 0x0b, 0x0a, 0x00, // bottom row
+        ];
+        let image = synthetic_image(
+            (2, 2),
+            32,
+            xlib::LSBFirst,
+            [0xff0000, 0x00ff00, 0x0000ff],
+            &mut data,
+        );
+
+        assert_eq!(
+            unsafe { convert(&image) }.unwrap(),
+            (data.to_vec(), PixelFormat::Bgra8)
+        );
+    }
+
+    #[test]
+    fn rgb565_is_rescaled() {
+        const MASKS: [u64; 3] = [0xf800, 0x07e0, 0x001f];
+        let expected = rgb(&[[255, 0, 0], [0, 255, 0], [0, 0, 255], [132, 130, 132]]);
+
+        let mut data = [0x00, 0xf8, 0xe0, 0x07, 0x1f, 0x00, 0x10, 0x84];
+        let image = synthetic_image((4, 1), 16, xlib::LSBFirst, MASKS, &mut data);
+        assert_eq!(unsafe { convert(&image) }.unwrap(), expected);
+
+        let mut data = [0xf8, 0x00, 0x07, 0xe0, 0x00, 0x1f, 0x84, 0x10];
+        let image = synthetic_image((4, 1), 16, xlib::MSBFirst, MASKS, &mut data);
+        assert_eq!(unsafe { convert(&image) }.unwrap(), expected);
+    }
+
+    #[test]
+    fn ten_bits_per_channel_is_truncated_to_eight() {
+        // 2-10-10-10, red 1023, green 512, blue 3
+        let pixel: u32 = 1023 << 20 | 512 << 10 | 3;
+        let mut data = pixel.to_le_bytes();
+        let image = synthetic_image(
+            (1, 1),
+            32,
+            xlib::LSBFirst,
+            [0x3ff00000, 0x000ffc00, 0x000003ff],
+            &mut data,
+        );
+
+        assert_eq!(unsafe { convert(&image) }.unwrap(), rgb(&[[255, 128, 0]]));
+    }
+
+    #[test]
+    fn packed_24_bits_per_pixel_with_row_padding() {
+        // two rows of one pixel, padded to 4 bytes
+        let mut data = [0x03, 0x02, 0x01, 0xee, 0x06, 0x05, 0x04, 0xee];
+        let image = synthetic_image(
+            (1, 2),
+            24,
+            xlib::LSBFirst,
+            [0xff0000, 0x00ff00, 0x0000ff],
+            &mut data,
+        );
+
+        assert_eq!(
+            unsafe { convert(&image) }.unwrap(),
+            rgb(&[[1, 2, 3], [4, 5, 6]])
+        );
+    }
+
+    #[test]
+    fn rgb332() {
+        // 3-3-2, the last pixel is red 4, green 2, blue 1
+        let mut data = [0b1110_0000, 0b0001_1100, 0b0000_0011, 0b1000_1001];
+        let image = synthetic_image((4, 1), 8, xlib::LSBFirst, [0xe0, 0x1c, 0x03], &mut data);
+
+        assert_eq!(
+            unsafe { convert(&image) }.unwrap(),
+            rgb(&[[255, 0, 0], [0, 255, 0], [0, 0, 255], [146, 73, 85]])
+        );
+    }
+
+    #[test]
+    fn unsupported_layouts_are_errors() {
+        let mut data = [0; 4];
+
+        let cases: &[(i32, [u64; 3])] = &[
+            // not a whole number of bytes
+            (4, [0x8, 0x4, 0x2]),
+            // split green channel
+            (16, [0xf800, 0x07c1, 0x001e]),
+            // empty channel
+            (32, [0xff0000, 0, 0xff]),
+            // mask wider than the pixel
+            (16, [0xff0000, 0x00ff00, 0x0000ff]),
+        ];
+
+        for &(bits_per_pixel, masks) in cases {
+            let image = synthetic_image((1, 1), bits_per_pixel, xlib::LSBFirst, masks, &mut data);
+
+            assert!(
+                matches!(
+                    unsafe { convert(&image) },
+                    Err(CaptureError::UnsupportedPixelFormat { .. })
+                ),
+                "{bits_per_pixel} bpp, masks {masks:x?}"
+            );
+        }
+    }
 }
@@ -2,7 +2,22 @@ struct Uniform {
     projection: mat4x4<f32>,
     mouse_position: vec2<f32>,
     flashlight: u32,
-    flashglith_radius: f32,
+    flashglith_radius: f32, // window pixels, or image pixels when anchored to the image
+    selection: vec4<f32>, // min x, min y, max x, max y
+    selecting: u32,
+    grid: u32,
+    grid_zoom: f32,
+    zoom: f32,
+    image_size: vec2<f32>,
+    flashlight_dim: f32, // how much of `flashlight_color` to mix in outside the light
+    flashlight_softness: f32, // width of the edge in window pixels
+    flashlight_color: vec4<f32>,
+    flashlight_shape: u32, // 0 circle, 1 ellipse, 2 rounded rectangle
+    flashlight_space: u32, // 0 follows the mouse in window pixels, 1 is anchored to the image
+    flashlight_anchor: vec2<f32>, // middle of the light in world space, when anchored to the image
+    loupe: u32,
+    loupe_radius: f32, // window pixels
+    loupe_magnification: f32, // on top of `zoom`
 };
 
 struct VertexInput {
@@ -13,6 +28,7 @@ struct VertexInput {
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) tex_coord: vec2<f32>,
+    @location(1) world_position: vec2<f32>, // image pixels, y up
 }
 
 @group(0) @binding(0)
@@ -24,6 +40,7 @@ fn vs_main(model: VertexInput) -> VertexOutput {
 
     out.position = data.projection * vec4(model.position, 1.0);
     out.tex_coord = model.tex_coord;
+    out.world_position = model.position.xy;
 
     return out;
 }
@@ -37,17 +54,132 @@ var s_diffuse: sampler;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    var mix: f32 = 0.0;
+    // BGRX screenshots have padding where alpha would be
+    var color = textureSample(t_diffuse, s_diffuse, in.tex_coord).rgb;
+
+    // what the grid is drawn for, the lens has its own
+    var world_position = in.world_position;
+    var zoom = data.zoom;
+
+    let lens_distance = length(in.position.xy - data.mouse_position);
+
+    if data.loupe == 1 {
+        // the world point under the mouse stays put, everything else moves towards it
+        let window_to_world = vec2(1.0, -1.0) / data.zoom;
+        let mouse = in.world_position + (data.mouse_position - in.position.xy) * window_to_world;
+        let magnified = mouse + (in.world_position - mouse) / data.loupe_magnification;
+
+        // world y goes up and texture y down. Images split into tiles only show the tile
+        // under each fragment, so the lens is cut off where it crosses from one to another
+        let texture_size = vec2<f32>(textureDimensions(t_diffuse));
+        let tex_coord = in.tex_coord + (magnified - in.world_position) * vec2(1.0, -1.0) / texture_size;
+
+        // sampled everywhere, the derivatives for picking a mip level need that
+        let lens_color = textureSample(t_diffuse, s_diffuse, tex_coord).rgb;
+
+        if lens_distance < data.loupe_radius {
+            color = lens_color;
+            world_position = magnified;
+            zoom *= data.loupe_magnification;
+        }
+    }
+
+    if data.grid == 1 {
+        color = pixel_grid(color, world_position, zoom);
+    }
+
+    if data.loupe == 1 {
+        // rim, about a window pixel and a half wide
+        let rim = 1.0 - smoothstep(0.75, 1.5, abs(lens_distance - data.loupe_radius));
+        color = mix(color, vec3(1.0), rim * 0.8);
+    }
 
     if data.flashlight == 1 {
-        if length(in.position.xy - data.mouse_position) < data.flashglith_radius {
-            mix = 0.0;
+        var distance: f32;
+        if data.flashlight_space == 1 {
+            // scaled to window pixels, the edge stays as soft at any zoom
+            distance = flashlight_distance(in.world_position - data.flashlight_anchor) * data.zoom;
+        } else {
+            distance = flashlight_distance(in.position.xy - data.mouse_position);
+        }
+
+        let outside = flashlight_edge(distance);
+        color = mix(color, data.flashlight_color.rgb, data.flashlight_dim * outside);
+    }
+
+    if data.selecting == 1 {
+        let p = in.position.xy;
+        let lo = data.selection.xy;
+        let hi = data.selection.zw;
+
+        if all(p >= lo) && all(p <= hi) {
+            // outline
+            if any(p < lo + 1.0) || any(p > hi - 1.0) {
+                return vec4(1.0, 1.0, 1.0, 1.0);
+            }
         } else {
-            mix = 0.9;
+            color = mix(color, vec3(0.0), 0.5);
         }
-    } else {
-        mix = 0.0;
     }
 
-    return mix(textureSample(t_diffuse, s_diffuse, in.tex_coord), vec4(0.0, 0.0, 0.0, 1.0), mix);
+    return vec4(color, 1.0);
+}
+
+// Signed distance from the edge of the flashlight, negative inside. `p` is relative to
+// the middle of the light, in the same units as the radius.
+fn flashlight_distance(p: vec2<f32>) -> f32 {
+    let r = data.flashglith_radius;
+
+    switch data.flashlight_shape {
+        // ellipse, wider than tall. Not an exact distance, but close enough for the edge
+        case 1u: {
+            let radii = vec2(r * 1.6, r);
+            return (length(p / radii) - 1.0) * r;
+        }
+
+        // rounded rectangle
+        case 2u: {
+            let corner = r * 0.3;
+            let q = abs(p) - vec2(r * 1.6, r) + corner;
+            return length(max(q, vec2(0.0))) + min(max(q.x, q.y), 0.0) - corner;
+        }
+
+        default: {
+            return length(p) - r;
+        }
+    }
+}
+
+// 0 inside the flashlight, 1 outside, fading over `flashlight_softness` pixels.
+fn flashlight_edge(distance: f32) -> f32 {
+    if data.flashlight_softness <= 0.0 {
+        return step(0.0, distance);
+    }
+
+    return smoothstep(0.0, data.flashlight_softness, distance);
+}
+
+// Thin lines between the image's pixels, fading in from `grid_zoom` to twice that.
+fn pixel_grid(color: vec3<f32>, world_position: vec2<f32>, zoom: f32) -> vec3<f32> {
+    let fade = smoothstep(data.grid_zoom, data.grid_zoom * 2.0, zoom);
+    if fade == 0.0 {
+        return color;
+    }
+
+    // image pixel coordinates, counted from the top left like the pixel data
+    let pixel = vec2(world_position.x, data.image_size.y - world_position.y);
+
+    // distance to the nearest pixel edge in window pixels, the image's outline is left alone
+    let inside = pixel > vec2(0.5) && pixel < data.image_size - 0.5;
+    let edge = abs(fract(pixel + 0.5) - 0.5) * zoom;
+    let distance = min(select(1e6, edge.x, inside.x), select(1e6, edge.y, inside.y));
+
+    // about a window pixel wide, anti-aliased
+    let line = 1.0 - smoothstep(0.5, 1.0, distance);
+
+    // dark lines on light pixels and the other way round
+    let luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    let line_color = select(vec3(0.0), vec3(1.0), luminance < 0.5);
+
+    return mix(color, line_color, line * fade * 0.35);
 }
@@ -1,77 +1,531 @@
 use anyhow::*;
 
+use crate::capture::PixelFormat;
+
+/// Tiles overlap their neighbours by this many pixels so linear filtering doesn't show
+/// seams between them.
+const TILE_BORDER: u32 = 1;
+
+/// An image on the GPU. Images larger than the device's maximum texture size are split
+/// into a grid of tiles, each with its own texture.
 pub struct Texture {
-    #[allow(unused)]
+    /// row by row, starting at the top left
+    pub tiles: Vec<Tile>,
+    /// (width, height) of the whole image
+    pub dimensions: (u32, u32),
+    pub linear_sampler: wgpu::Sampler,
+    pub nearest_sampler: wgpu::Sampler,
+    pub format: PixelFormat,
+
+    // renders each mip level from the one above it
+    blit_pipeline: wgpu::RenderPipeline,
+}
+
+pub struct Tile {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
+    /// top left corner of the part of the image this tile shows, in image pixels
+    pub origin: (u32, u32),
+    /// (width, height) of that part
+    pub size: (u32, u32),
+    /// top left corner of the texture in image pixels, it also holds the border shared
+    /// with the neighbouring tiles
+    texture_origin: (u32, u32),
+    /// one view per mip level, and a bind group to sample each level but the last
+    mip_views: Vec<wgpu::TextureView>,
+    mip_bind_groups: Vec<wgpu::BindGroup>,
+    /// written to since the mipmaps were last generated
+    dirty: bool,
+}
+
+impl Tile {
+    /// Texture coordinates of the top left and bottom right corners of the part this tile
+    /// shows.
+    pub fn tex_coords(&self) -> ([f32; 2], [f32; 2]) {
+        tex_coords(
+            self.origin,
+            self.size,
+            self.texture_origin,
+            (self.texture.width(), self.texture.height()),
+        )
+    }
 }
 
 impl Texture {
     pub fn from_bytes(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        bytes: &[u8], 
+        bytes: &[u8],
         dimensions: (u32, u32),
-        label: &str
+        format: PixelFormat,
+        label: &str,
     ) -> Result<Self> {
-        Self::from_image(device, queue, &bytes, dimensions, Some(label))
+        Self::from_image(device, queue, bytes, dimensions, format, Some(label))
     }
 
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        rgba: &[u8],
+        pixels: &[u8],
         dimensions: (u32, u32),
-        label: Option<&str>
+        format: PixelFormat,
+        label: Option<&str>,
     ) -> Result<Self> {
-        let size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
-            depth_or_array_layers: 1,
-        };
-        let texture = device.create_texture(
-            &wgpu::TextureDescriptor {
-                label,
-                size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
-            }
+        ensure!(
+            dimensions.0 > 0 && dimensions.1 > 0,
+            "the image is empty ({}x{})",
+            dimensions.0,
+            dimensions.1
         );
 
-        queue.write_texture(
-            wgpu::TexelCopyTextureInfo {
-                aspect: wgpu::TextureAspect::All,
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            rgba,
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
+        // sampling a BGRA texture gives RGBA, no need to swap channels ourselves
+        let texture_format = match format {
+            PixelFormat::Rgba8 => wgpu::TextureFormat::Rgba8UnormSrgb,
+            PixelFormat::Bgra8 => wgpu::TextureFormat::Bgra8UnormSrgb,
+        };
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("blit.wgsl"));
+        let blit_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mipmap Pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[],
             },
-            size,
-        );
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(texture_format.into())],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let blit_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Mipmap Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let max = device.limits().max_texture_dimension_2d;
+        let columns = spans(dimensions.0, max);
+        let rows = spans(dimensions.1, max);
+
+        let mut tiles = Vec::with_capacity(columns.len() * rows.len());
+        for &(y, height) in &rows {
+            for &(x, width) in &columns {
+                let (left, texture_width) = with_border((x, width), dimensions.0);
+                let (top, texture_height) = with_border((y, height), dimensions.1);
+
+                let size = wgpu::Extent3d {
+                    width: texture_width,
+                    height: texture_height,
+                    depth_or_array_layers: 1,
+                };
+
+                let texture = device.create_texture(&wgpu::TextureDescriptor {
+                    label,
+                    size,
+                    // all the way down to 1x1
+                    mip_level_count: size.max_mips(wgpu::TextureDimension::D2),
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: texture_format,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING
+                        | wgpu::TextureUsages::COPY_DST
+                        | wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                });
+
+                let mip_views: Vec<_> = (0..texture.mip_level_count())
+                    .map(|level| {
+                        texture.create_view(&wgpu::TextureViewDescriptor {
+                            base_mip_level: level,
+                            mip_level_count: Some(1),
+                            ..Default::default()
+                        })
+                    })
+                    .collect();
+
+                let mip_bind_groups = mip_views[..mip_views.len() - 1]
+                    .iter()
+                    .map(|view| {
+                        device.create_bind_group(&wgpu::BindGroupDescriptor {
+                            label: None,
+                            layout: &blit_pipeline.get_bind_group_layout(0),
+                            entries: &[
+                                wgpu::BindGroupEntry {
+                                    binding: 0,
+                                    resource: wgpu::BindingResource::TextureView(view),
+                                },
+                                wgpu::BindGroupEntry {
+                                    binding: 1,
+                                    resource: wgpu::BindingResource::Sampler(&blit_sampler),
+                                },
+                            ],
+                        })
+                    })
+                    .collect();
+
+                tiles.push(Tile {
+                    view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    texture,
+                    origin: (x, y),
+                    size: (width, height),
+                    texture_origin: (left, top),
+                    mip_views,
+                    mip_bind_groups,
+                    dirty: false,
+                });
+            }
+        }
+
+        let linear_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        // sharp pixels when magnifying, zoomed out views still go through the mipmaps
+        let nearest_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let mut texture = Self {
+            tiles,
+            dimensions,
+            linear_sampler,
+            nearest_sampler,
+            format,
+            blit_pipeline,
+        };
+
+        texture.write(queue, (0, 0), dimensions, pixels);
+        texture.generate_mipmaps(device, queue);
+
+        Ok(texture)
+    }
+
+    pub fn sampler(&self, filter: wgpu::FilterMode) -> &wgpu::Sampler {
+        match filter {
+            wgpu::FilterMode::Linear => &self.linear_sampler,
+            wgpu::FilterMode::Nearest => &self.nearest_sampler,
+        }
+    }
+
+    /// Redraws every mip level below the first from the one above it, call after changing
+    /// the pixels. Only tiles written to since the last call are redrawn.
+    pub fn generate_mipmaps(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !self.tiles.iter().any(|tile| tile.dirty) {
+            return;
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mipmap Encoder"),
+        });
+
+        for tile in self.tiles.iter_mut().filter(|tile| tile.dirty) {
+            tile.dirty = false;
+
+            for (bind_group, target) in tile.mip_bind_groups.iter().zip(&tile.mip_views[1..]) {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Mipmap Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: target,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+
+                render_pass.set_pipeline(&self.blit_pipeline);
+                render_pass.set_bind_group(0, bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    /// Replaces the `size` texels starting at `origin` (counted from the top left, like the
+    /// pixel data) with `pixels`. Only touches the first mip level, see
+    /// [`Texture::generate_mipmaps`].
+    pub fn write(
+        &mut self,
+        queue: &wgpu::Queue,
+        origin: (u32, u32),
+        size: (u32, u32),
+        pixels: &[u8],
+    ) {
+        let bytes_per_pixel = self.format.bytes_per_pixel();
+
+        for tile in &mut self.tiles {
+            let texture_size = (tile.texture.width(), tile.texture.height());
+            let Some((texel, offset, extent)) = clip(
+                origin,
+                size,
+                tile.texture_origin,
+                texture_size,
+                bytes_per_pixel,
+            ) else {
+                continue;
+            };
+
+            tile.dirty = true;
+
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &tile.texture,
+                    mip_level: 0,
+                    origin: texel,
+                },
+                pixels,
+                wgpu::TexelCopyBufferLayout {
+                    offset,
+                    bytes_per_row: Some(bytes_per_pixel * size.0),
+                    rows_per_image: Some(size.1),
+                },
+                extent,
+            );
+        }
+    }
+}
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(
-            &wgpu::SamplerDescriptor {
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
-                mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Linear,
-                mipmap_filter: wgpu::FilterMode::Linear,
-                ..Default::default()
+/// Splits `length` pixels into (start, length) pieces that fit in a `max` sized texture
+/// together with their borders.
+fn spans(length: u32, max: u32) -> Vec<(u32, u32)> {
+    if length <= max {
+        return vec![(0, length)];
+    }
+
+    let step = max - 2 * TILE_BORDER;
+
+    (0..length)
+        .step_by(step as _)
+        .map(|start| (start, step.min(length - start)))
+        .collect()
+}
+
+/// Grows a span from [`spans`] by the border on both sides, without leaving the `length`
+/// pixels of the image. Returns the (start, length) of the texture holding it.
+fn with_border((start, length): (u32, u32), total: u32) -> (u32, u32) {
+    let first = start.saturating_sub(TILE_BORDER);
+    let end = (start + length + TILE_BORDER).min(total);
+
+    (first, end - first)
+}
+
+/// Texture coordinates of the top left and bottom right corners of the `size` pixels at
+/// `origin`, in a texture of `texture_size` starting at `texture_origin`.
+fn tex_coords(
+    origin: (u32, u32),
+    size: (u32, u32),
+    texture_origin: (u32, u32),
+    texture_size: (u32, u32),
+) -> ([f32; 2], [f32; 2]) {
+    let width = texture_size.0 as f32;
+    let height = texture_size.1 as f32;
+
+    let left = (origin.0 - texture_origin.0) as f32;
+    let top = (origin.1 - texture_origin.1) as f32;
+
+    (
+        [left / width, top / height],
+        [
+            (left + size.0 as f32) / width,
+            (top + size.1 as f32) / height,
+        ],
+    )
+}
+
+/// Clips a write of `size` pixels at `origin` to a texture of `texture_size` starting at
+/// `texture_origin`. Returns where in the texture the write lands, the byte offset of its
+/// first pixel in the written data and its extent, or `None` if they don't overlap.
+fn clip(
+    origin: (u32, u32),
+    size: (u32, u32),
+    texture_origin: (u32, u32),
+    texture_size: (u32, u32),
+    bytes_per_pixel: u32,
+) -> Option<(wgpu::Origin3d, wgpu::BufferAddress, wgpu::Extent3d)> {
+    let left = origin.0.max(texture_origin.0);
+    let top = origin.1.max(texture_origin.1);
+    let right = (origin.0 + size.0).min(texture_origin.0 + texture_size.0);
+    let bottom = (origin.1 + size.1).min(texture_origin.1 + texture_size.1);
+
+    if right <= left || bottom <= top {
+        return None;
+    }
+
+    let texel = wgpu::Origin3d {
+        x: left - texture_origin.0,
+        y: top - texture_origin.1,
+        z: 0,
+    };
+
+    // start at the first pixel inside the texture, rows keep the full stride
+    let offset = ((top - origin.1) * size.0 + (left - origin.0)) * bytes_per_pixel;
+
+    let extent = wgpu::Extent3d {
+        width: right - left,
+        height: bottom - top,
+        depth_or_array_layers: 1,
+    };
+
+    Some((texel, offset as _, extent))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The textures holding the tiles of `length` pixels, as (start, length).
+    fn textures(length: u32, max: u32) -> Vec<(u32, u32)> {
+        spans(length, max)
+            .into_iter()
+            .map(|span| with_border(span, length))
+            .collect()
+    }
+
+    /// Every pixel is in exactly one span, in order.
+    fn assert_covers(spans: &[(u32, u32)], length: u32) {
+        let mut end = 0;
+        for &(start, span) in spans {
+            assert_eq!(start, end);
+            assert!(span > 0);
+            end = start + span;
+        }
+        assert_eq!(end, length);
+    }
+
+    #[test]
+    fn spans_fit_in_one_texture() {
+        assert_eq!(spans(100, 100), vec![(0, 100)]);
+        assert_eq!(spans(1, 100), vec![(0, 1)]);
+        assert_eq!(textures(100, 100), vec![(0, 100)]);
+    }
+
+    #[test]
+    fn spans_one_over_the_limit() {
+        let spans = spans(101, 100);
+
+        assert_eq!(spans, vec![(0, 98), (98, 3)]);
+        assert_covers(&spans, 101);
+        assert_eq!(textures(101, 100), vec![(0, 99), (97, 4)]);
+    }
+
+    #[test]
+    fn spans_exact_multiples_of_the_step() {
+        for count in 2..5 {
+            let length = count * 98;
+            let spans = spans(length, 100);
+
+            assert_eq!(spans.len(), count as usize);
+            assert!(spans.iter().all(|&(_, span)| span == 98));
+            assert_covers(&spans, length);
+        }
+    }
+
+    #[test]
+    fn tiles_with_borders_fit() {
+        for max in [3, 4, 100, 8192] {
+            for length in [
+                1,
+                max - 1,
+                max,
+                max + 1,
+                2 * max,
+                3 * (max - 2),
+                3 * (max - 2) + 1,
+            ] {
+                let spans = spans(length, max);
+                assert_covers(&spans, length);
+
+                for (&(start, span), (first, texture)) in spans.iter().zip(textures(length, max)) {
+                    assert!(
+                        texture <= max,
+                        "{length} px at most {max}: {texture} px texture"
+                    );
+                    // the border is there on both sides unless at the edge of the image
+                    assert_eq!(first, start.saturating_sub(TILE_BORDER));
+                    assert!(first + texture >= (start + span + TILE_BORDER).min(length));
+                }
             }
+        }
+    }
+
+    #[test]
+    fn tex_coords_of_a_single_texture() {
+        assert_eq!(
+            tex_coords((0, 0), (100, 50), (0, 0), (100, 50)),
+            ([0.0, 0.0], [1.0, 1.0])
         );
-        
-        Ok(Self { texture, view, sampler })
+    }
+
+    #[test]
+    fn tex_coords_skip_the_border() {
+        // a middle tile, the texture starts one pixel before it on both axes
+        let (top_left, bottom_right) = tex_coords((98, 98), (98, 98), (97, 97), (100, 100));
+
+        assert_eq!(top_left, [0.01, 0.01]);
+        assert_eq!(bottom_right, [0.99, 0.99]);
+    }
+
+    #[test]
+    fn clip_inside_the_texture() {
+        let (texel, offset, extent) = clip((10, 20), (4, 3), (0, 0), (100, 100), 4).unwrap();
+
+        assert_eq!((texel.x, texel.y), (10, 20));
+        assert_eq!(offset, 0);
+        assert_eq!((extent.width, extent.height), (4, 3));
+    }
+
+    #[test]
+    fn clip_to_the_texture() {
+        // a 10x10 write at (95, 90) into a texture covering (97, 97) to (197, 197)
+        let (texel, offset, extent) = clip((95, 90), (10, 10), (97, 97), (100, 100), 4).unwrap();
+
+        assert_eq!((texel.x, texel.y), (0, 0));
+        // skips 7 rows of 10 pixels and 2 pixels into the 8th
+        assert_eq!(offset, (7 * 10 + 2) * 4);
+        assert_eq!((extent.width, extent.height), (8, 3));
+
+        // and the part sticking out of the bottom right
+        let (texel, offset, extent) = clip((195, 190), (10, 10), (97, 97), (100, 100), 4).unwrap();
+
+        assert_eq!((texel.x, texel.y), (98, 93));
+        assert_eq!(offset, 0);
+        assert_eq!((extent.width, extent.height), (2, 7));
+    }
+
+    #[test]
+    fn clip_outside_the_texture() {
+        assert!(clip((0, 0), (10, 10), (10, 0), (100, 100), 4).is_none());
+        assert!(clip((0, 0), (10, 10), (0, 10), (100, 100), 4).is_none());
+        assert!(clip((200, 0), (10, 10), (97, 0), (100, 100), 4).is_none());
+        assert!(clip((0, 0), (0, 10), (0, 0), (100, 100), 4).is_none());
     }
 }